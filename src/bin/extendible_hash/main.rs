@@ -0,0 +1,754 @@
+use std::{
+    io::stdout,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    terminal::{Clear, ClearType},
+    ExecutableCommand,
+};
+use extendible_hash::{
+    extendible_hash::DEFAULT_MAX_GLOBAL_DEPTH, hash_function, Error, HashAlt1, HashAlt2, HashText,
+    KeyIndex, Record, SecondaryIndex, SyncPolicy, Table,
+};
+use inquire::{Select, Text};
+use random_util::{random_string, unique_random_numbers};
+
+mod random_util;
+
+enum Menu {
+    GeraHash,
+    Novo,
+    Principal,
+    Inserir,
+    Atualizar,
+    Remover,
+    Buscar,
+    Verificar,
+    Indice,
+    Autosalvar,
+    Random,
+}
+
+// Indices que podem ser criados pelo menu
+const PRESETS: [&str; 3] = ["prefixo", "tamanho", "modulo"];
+
+// Cada mudanca vai para hash.wal antes de aparecer na tela
+const SYNC: SyncPolicy = SyncPolicy::Always;
+
+fn main() {
+    let mut table = new_table(
+        1,
+        4,
+        hash_function::LOW_BITS_ID,
+        DEFAULT_MAX_GLOBAL_DEPTH,
+        1.0,
+    )
+    .unwrap();
+
+    let mut m = Menu::GeraHash;
+    let mut stdout = stdout();
+
+    let mut autosave = Autosave {
+        interval: None,
+        last: Instant::now(),
+        error: None,
+    };
+
+    loop {
+        if let Some(e) = autosave.error.take() {
+            show_error(&mut table, &mut autosave, &e);
+        }
+
+        stdout.execute(Clear(ClearType::All)).unwrap();
+        let header = "=".to_string().repeat(20);
+        println!("{header} HASH TABLE {header}\n",);
+        print!("{}", table.records());
+
+        match m {
+            Menu::GeraHash => {
+                let option = ask(&mut table, &mut autosave, move || {
+                    Select::new("Gerar Hash", vec!["Novo", "Carregar", "Aleatorio", "Sair"])
+                        .prompt()
+                });
+
+                match option {
+                    Ok("Novo") => m = Menu::Novo,
+                    Ok("Carregar") => match load() {
+                        Ok(t) => {
+                            table = t;
+                            m = Menu::Principal;
+                        }
+                        Err(e) => show_error(&mut table, &mut autosave, &e),
+                    },
+                    Ok("Aleatorio") => m = Menu::Random,
+                    Ok(_) => {
+                        if let Err(e) = save_quit(&mut table) {
+                            println!("{e}");
+                        }
+                        break;
+                    }
+                    Err(_) => continue,
+                }
+            }
+            Menu::Novo => {
+                let gd = ask(&mut table, &mut autosave, move || {
+                    Text::new("Global Depth inicial: ")
+                        .with_default("2")
+                        .with_validator(|n: &str| {
+                            // O mesmo tipo do parse abaixo
+                            let parsed: Result<u8, _> = n.parse();
+                            if parsed.is_ok_and(|d| d <= DEFAULT_MAX_GLOBAL_DEPTH) {
+                                Ok(inquire::validator::Validation::Valid)
+                            } else {
+                                Ok(inquire::validator::Validation::Invalid(
+                                    format!(
+                                        "Tem que ser um inteiro de 0 a {DEFAULT_MAX_GLOBAL_DEPTH}"
+                                    )
+                                    .into(),
+                                ))
+                            }
+                        })
+                        .prompt()
+                });
+
+                let bs = ask(&mut table, &mut autosave, move || {
+                    Text::new("Tamanho do bucket: ")
+                        .with_default("4")
+                        .with_validator(|n: &str| {
+                            let parsed: Result<u32, _> = n.parse();
+                            if parsed.is_ok() {
+                                Ok(inquire::validator::Validation::Valid)
+                            } else {
+                                Ok(inquire::validator::Validation::Invalid(
+                                    "Tem que ser um inteiro".into(),
+                                ))
+                            }
+                        })
+                        .prompt()
+                });
+
+                let md_default = DEFAULT_MAX_GLOBAL_DEPTH.to_string();
+                let md = ask(&mut table, &mut autosave, move || {
+                    Text::new("Global Depth maxima: ")
+                        .with_default(&md_default)
+                        .with_validator(|n: &str| {
+                            let parsed: Result<u8, _> = n.parse();
+                            if parsed.is_ok() {
+                                Ok(inquire::validator::Validation::Valid)
+                            } else {
+                                Ok(inquire::validator::Validation::Invalid(
+                                    "Tem que ser um inteiro de 0 a 255".into(),
+                                ))
+                            }
+                        })
+                        .prompt()
+                });
+
+                let mt = ask(&mut table, &mut autosave, move || {
+                    Text::new("Limiar para juntar baldes (0 a 1): ")
+                        .with_default("1")
+                        .with_validator(|n: &str| {
+                            // NaN tambem fica fora do intervalo
+                            let parsed: Result<f32, _> = n.parse();
+                            if parsed.is_ok_and(|t| (0.0..=1.0).contains(&t)) {
+                                Ok(inquire::validator::Validation::Valid)
+                            } else {
+                                Ok(inquire::validator::Validation::Invalid(
+                                    "Tem que ser um numero de 0 a 1".into(),
+                                ))
+                            }
+                        })
+                        .prompt()
+                });
+
+                let hf = ask(&mut table, &mut autosave, move || {
+                    Select::new("Funcao de hash: ", vec!["Bits baixos", "FxHash", "SipHash"])
+                        .prompt()
+                });
+
+                let gd: u8 = gd.unwrap().parse().unwrap();
+                let bs: u32 = bs.unwrap().parse().unwrap();
+                let md: u8 = md.unwrap().parse().unwrap();
+                let mt: f32 = mt.unwrap().parse().unwrap();
+
+                let hf = match hf {
+                    Ok("FxHash") => hash_function::FX_HASH_ID,
+                    Ok("SipHash") => hash_function::SIP_HASH_ID,
+                    _ => hash_function::LOW_BITS_ID,
+                };
+
+                match new_table(gd, bs, hf, md, mt).and_then(|mut t| {
+                    t.start_log("hash", SYNC)?;
+                    Ok(t)
+                }) {
+                    Ok(t) => {
+                        table = t;
+                        m = Menu::Principal;
+                    }
+                    Err(e) => {
+                        show_error(&mut table, &mut autosave, &e);
+                        m = Menu::GeraHash;
+                    }
+                }
+            }
+
+            Menu::Principal => {
+                let option = ask(&mut table, &mut autosave, move || {
+                    Select::new(
+                        "O que voce quer fazer?",
+                        vec![
+                            "Inserir",
+                            "Atualizar",
+                            "Remover",
+                            "Buscar",
+                            "Verificar",
+                            "Criar indice",
+                            "Autosalvar",
+                            "Sair",
+                        ],
+                    )
+                    .prompt()
+                });
+
+                match option {
+                    Ok("Inserir") => m = Menu::Inserir,
+                    Ok("Atualizar") => m = Menu::Atualizar,
+                    Ok("Remover") => m = Menu::Remover,
+                    Ok("Buscar") => m = Menu::Buscar,
+                    Ok("Verificar") => m = Menu::Verificar,
+                    Ok("Criar indice") => m = Menu::Indice,
+                    Ok("Autosalvar") => m = Menu::Autosalvar,
+                    Ok(_) => {
+                        if let Err(e) = save_quit(&mut table) {
+                            println!("{e}");
+                        }
+                        break;
+                    }
+                    Err(_) => continue,
+                }
+            }
+            Menu::Inserir => {
+                let nseq = ask(&mut table, &mut autosave, move || {
+                    Text::new("Nseq: ")
+                        .with_help_message("Digite um valor para o campo nseq do registro")
+                        .with_validator(|n: &str| {
+                            let parsed: Result<i32, _> = n.parse();
+                            if parsed.is_ok() {
+                                Ok(inquire::validator::Validation::Valid)
+                            } else {
+                                Ok(inquire::validator::Validation::Invalid(
+                                    "Tem que ser um inteiro".into(),
+                                ))
+                            }
+                        })
+                        .prompt()
+                });
+
+                let nseq: i32 = nseq.unwrap().parse().unwrap();
+
+                let text = ask(&mut table, &mut autosave, move || {
+                    Text::new("Text: ")
+                        .with_help_message("Digite um valor para o campo nseq do registro")
+                        .prompt()
+                })
+                .unwrap();
+
+                if let Err(e) = table.insert(Record { nseq, text }) {
+                    show_error(&mut table, &mut autosave, &e);
+                }
+
+                m = Menu::Principal;
+            }
+            Menu::Atualizar => {
+                let nseq = ask(&mut table, &mut autosave, move || {
+                    Text::new("Nseq: ")
+                        .with_help_message("Digite a chave (nseq) do registro a atualizar")
+                        .with_validator(|n: &str| {
+                            let parsed: Result<i32, _> = n.parse();
+                            if parsed.is_ok() {
+                                Ok(inquire::validator::Validation::Valid)
+                            } else {
+                                Ok(inquire::validator::Validation::Invalid(
+                                    "Tem que ser um inteiro".into(),
+                                ))
+                            }
+                        })
+                        .prompt()
+                });
+
+                let nseq: i32 = nseq.unwrap().parse().unwrap();
+
+                let text = ask(&mut table, &mut autosave, move || {
+                    Text::new("Text: ")
+                        .with_help_message("Digite o novo valor para o campo text do registro")
+                        .prompt()
+                })
+                .unwrap();
+
+                // O registro fica no mesmo slot, so as chaves secundarias mudam
+                if let Err(e) = table.update(Record { nseq, text }) {
+                    show_error(&mut table, &mut autosave, &e);
+                }
+
+                m = Menu::Principal;
+            }
+            Menu::Remover => {
+                let alt = ask(&mut table, &mut autosave, move || {
+                    Select::new(
+                        "Qual tipo de chave: ",
+                        vec!["Primaria (nseq)", "Secundaria (text + nseq)"],
+                    )
+                    .prompt()
+                });
+
+                match alt {
+                    Ok("Primaria (nseq)") => {
+                        let nseq = ask(&mut table, &mut autosave, move || {
+                            Text::new("Nseq: ")
+                                .with_help_message("Digite a chave (nseq) para remocao: ")
+                                .with_validator(|n: &str| {
+                                    let parsed: Result<i32, _> = n.parse();
+                                    if parsed.is_ok() {
+                                        Ok(inquire::validator::Validation::Valid)
+                                    } else {
+                                        Ok(inquire::validator::Validation::Invalid(
+                                            "Tem que ser um inteiro".into(),
+                                        ))
+                                    }
+                                })
+                                .prompt()
+                        });
+
+                        let nseq: i32 = nseq.unwrap().parse().unwrap();
+
+                        if let Err(e) = table.remove(nseq) {
+                            show_error(&mut table, &mut autosave, &e);
+                        }
+                    }
+                    Ok("Secundaria (text + nseq)") => {
+                        let text = ask(&mut table, &mut autosave, move || {
+                            Text::new("Text: ")
+                                .with_help_message("Digite a chave (nseq) para remocao: ")
+                                .prompt()
+                        })
+                        .unwrap();
+
+                        let nseq = ask(&mut table, &mut autosave, move || {
+                            Text::new("Nseq: ")
+                                .with_help_message("Digite a chave (nseq) para remocao: ")
+                                .with_validator(|n: &str| {
+                                    let parsed: Result<i32, _> = n.parse();
+                                    if parsed.is_ok() {
+                                        Ok(inquire::validator::Validation::Valid)
+                                    } else {
+                                        Ok(inquire::validator::Validation::Invalid(
+                                            "Tem que ser um inteiro".into(),
+                                        ))
+                                    }
+                                })
+                                .prompt()
+                        });
+
+                        let nseq: i32 = nseq.unwrap().parse().unwrap();
+
+                        // So remove se o texto tambem bate
+                        if let Err(e) = table.remove_by("alt2", &Record { nseq, text }) {
+                            show_error(&mut table, &mut autosave, &e);
+                        }
+                    }
+                    _ => {}
+                }
+
+                m = Menu::Principal;
+            }
+            Menu::Buscar => {
+                let alt = ask(&mut table, &mut autosave, move || {
+                    Select::new(
+                        "Qual tipo de chave: ",
+                        vec![
+                            "Primaria (nseq)",
+                            "Secundaria (text + nseq)",
+                            "Texto",
+                            "Outro indice",
+                        ],
+                    )
+                    .prompt()
+                });
+
+                if let Ok("Outro indice") = alt {
+                    // Nomes de PRESETS, que duram o bastante para a thread do prompt
+                    let names: Vec<&str> = PRESETS
+                        .into_iter()
+                        .filter(|n| table.index_names().any(|i| i == *n))
+                        .collect();
+
+                    if let Ok(name) = ask(&mut table, &mut autosave, move || {
+                        Select::new("Indice: ", names).prompt()
+                    }) {
+                        let name = name.to_string();
+
+                        // O registro de exemplo so precisa dos campos usados pela chave
+                        let text = ask(&mut table, &mut autosave, move || {
+                            Text::new("Text: ")
+                                .with_help_message("Digite o campo text do registro de exemplo")
+                                .prompt()
+                        })
+                        .unwrap();
+
+                        let nseq = ask(&mut table, &mut autosave, move || {
+                            Text::new("Nseq: ")
+                                .with_help_message("Digite o campo nseq do registro de exemplo")
+                                .with_default("0")
+                                .with_validator(|n: &str| {
+                                    let parsed: Result<i32, _> = n.parse();
+                                    if parsed.is_ok() {
+                                        Ok(inquire::validator::Validation::Valid)
+                                    } else {
+                                        Ok(inquire::validator::Validation::Invalid(
+                                            "Tem que ser um inteiro".into(),
+                                        ))
+                                    }
+                                })
+                                .prompt()
+                        });
+
+                        let nseq: i32 = nseq.unwrap().parse().unwrap();
+
+                        match table.find(&name, &Record { nseq, text }) {
+                            Ok(rs) if rs.is_empty() => println!("Nenhum registro encontrado"),
+                            Ok(rs) => {
+                                for r in rs {
+                                    println!("{} - {}", r.nseq, r.text)
+                                }
+                            }
+                            Err(e) => println!("{e}"),
+                        }
+
+                        ask(&mut table, &mut autosave, move || {
+                            Select::new("", vec!["Voltar"]).prompt()
+                        })
+                        .unwrap();
+                    }
+
+                    m = Menu::Principal;
+                    continue;
+                }
+
+                let text = match alt {
+                    Ok("Secundaria (text + nseq)") | Ok("Texto") => Some(
+                        ask(&mut table, &mut autosave, move || {
+                            Text::new("Text: ")
+                                .with_help_message("Digite o campo text da chave")
+                                .prompt()
+                        })
+                        .unwrap(),
+                    ),
+                    _ => None,
+                };
+
+                let nseq = match alt {
+                    Ok("Texto") => None,
+                    _ => Some(ask(&mut table, &mut autosave, move || {
+                        Text::new("Nseq: ")
+                            .with_help_message("Digite a chave (nseq) para busca: ")
+                            .with_validator(|n: &str| {
+                                let parsed: Result<i32, _> = n.parse();
+                                if parsed.is_ok() {
+                                    Ok(inquire::validator::Validation::Valid)
+                                } else {
+                                    Ok(inquire::validator::Validation::Invalid(
+                                        "Tem que ser um inteiro".into(),
+                                    ))
+                                }
+                            })
+                            .prompt()
+                    })),
+                };
+
+                let nseq: Option<i32> = nseq.map(|n| n.unwrap().parse().unwrap());
+
+                let f = match (text, nseq) {
+                    (Some(text), Some(nseq)) => table.find("alt2", &Record { nseq, text }),
+                    // Todos os registros com o texto
+                    (Some(text), None) => table.find("text", &Record { nseq: 0, text }),
+                    (None, Some(nseq)) => table.get(nseq).map(|r| vec![r]),
+                    (None, None) => Ok(vec![]),
+                };
+
+                match f {
+                    Ok(rs) if rs.is_empty() => println!("Nenhum registro encontrado"),
+                    Ok(rs) => {
+                        for r in rs {
+                            println!("{} - {}", r.nseq, r.text)
+                        }
+                    }
+                    Err(e) => println!("{e}"),
+                }
+
+                ask(&mut table, &mut autosave, move || {
+                    Select::new("", vec!["Voltar"]).prompt()
+                })
+                .unwrap();
+
+                m = Menu::Principal;
+            }
+            Menu::Verificar => {
+                match table.check_consistency() {
+                    Ok(()) => println!("Indices OK"),
+                    Err(e) => println!("{e}"),
+                }
+
+                let option = ask(&mut table, &mut autosave, move || {
+                    Select::new("", vec!["Voltar", "Reconstruir indices"]).prompt()
+                });
+
+                if let Ok("Reconstruir indices") = option {
+                    if let Err(e) = table.rebuild() {
+                        show_error(&mut table, &mut autosave, &e);
+                    }
+                }
+
+                m = Menu::Principal;
+            }
+            Menu::Indice => {
+                let names: Vec<&str> = PRESETS
+                    .into_iter()
+                    .filter(|n| table.index(n).is_err())
+                    .collect();
+
+                let option = ask(&mut table, &mut autosave, move || {
+                    Select::new(
+                        "Chave do novo indice: ",
+                        names
+                            .iter()
+                            .map(|n| match *n {
+                                "prefixo" => "prefixo (3 primeiras letras do text)",
+                                "tamanho" => "tamanho (tamanho do text)",
+                                _ => "modulo (nseq modulo 10)",
+                            })
+                            .collect(),
+                    )
+                    .prompt()
+                });
+
+                if let Ok(option) = option {
+                    let name = option.split(' ').next().unwrap();
+
+                    // O indice e construido com os registros ja inseridos
+                    if let Err(e) = preset(name).and_then(|i| table.register(name, i)) {
+                        show_error(&mut table, &mut autosave, &e);
+                    }
+                }
+
+                m = Menu::Principal;
+            }
+            Menu::Autosalvar => {
+                let default = autosave.interval.map_or(60, |d| d.as_secs()).to_string();
+                let secs = ask(&mut table, &mut autosave, move || {
+                    Text::new("Intervalo do autosalvamento em segundos (0 desliga): ")
+                        .with_default(&default)
+                        .with_validator(|n: &str| {
+                            let parsed: Result<u64, _> = n.parse();
+                            if parsed.is_ok() {
+                                Ok(inquire::validator::Validation::Valid)
+                            } else {
+                                Ok(inquire::validator::Validation::Invalid(
+                                    "Tem que ser um inteiro".into(),
+                                ))
+                            }
+                        })
+                        .prompt()
+                });
+
+                if let Ok(secs) = secs {
+                    let secs: u64 = secs.parse().unwrap();
+
+                    autosave.interval = (secs > 0).then(|| Duration::from_secs(secs));
+                    autosave.last = Instant::now();
+                }
+
+                m = Menu::Principal;
+            }
+            Menu::Random => {
+                let n = ask(&mut table, &mut autosave, move || {
+                    Text::new("Quantidade de registros: ")
+                        .with_default("100")
+                        .with_help_message("Sera gerado um hash novo com n registros aleatórios")
+                        .with_validator(|e: &str| {
+                            let parsed: Result<usize, _> = e.parse();
+                            if parsed.is_ok() {
+                                Ok(inquire::validator::Validation::Valid)
+                            } else {
+                                Ok(inquire::validator::Validation::Invalid(
+                                    "Tem que ser um inteiro nao negativo".into(),
+                                ))
+                            }
+                        })
+                        .prompt()
+                });
+
+                let n: usize = n.unwrap().parse().unwrap();
+                let bs: u32;
+
+                let log_n = (n as f64).log2().ceil() as u8;
+
+                if n < 100 {
+                    bs = 4;
+                } else if n < 1000 {
+                    bs = 8;
+                } else {
+                    bs = 16;
+                }
+
+                let gd = log_n.saturating_sub((bs as f64).log2() as u8);
+
+                table = new_table(
+                    gd,
+                    bs,
+                    hash_function::LOW_BITS_ID,
+                    DEFAULT_MAX_GLOBAL_DEPTH,
+                    1.0,
+                )
+                .unwrap();
+
+                if let Err(e) = rand_hash_values(&mut table, n) {
+                    show_error(&mut table, &mut autosave, &e);
+                }
+
+                if let Err(e) = table.start_log("hash", SYNC) {
+                    show_error(&mut table, &mut autosave, &e);
+                }
+                m = Menu::Principal;
+            }
+        }
+    }
+}
+
+fn show_error(table: &mut Table, autosave: &mut Autosave, e: &Error) {
+    println!("{e}");
+
+    let _ = ask(table, autosave, move || {
+        Select::new("", vec!["Voltar"]).prompt()
+    });
+}
+
+/// Salva a tabela a cada `interval`, tambem enquanto um prompt espera o
+/// usuario.
+struct Autosave {
+    // Desligado ate ser configurado no menu
+    interval: Option<Duration>,
+    last: Instant,
+    // Erro de um salvamento feito durante um prompt, mostrado depois dele
+    error: Option<Error>,
+}
+
+impl Autosave {
+    fn save(&mut self, table: &mut Table) {
+        // So os baldes que mudaram sao escritos
+        if let Err(e) = table.sync() {
+            self.error = Some(e);
+        }
+
+        self.last = Instant::now();
+    }
+}
+
+/// Mostra o prompt numa outra thread e, enquanto a resposta nao vem, salva
+/// a tabela a cada intervalo do autosalvamento.
+fn ask<T: Send + 'static>(
+    table: &mut Table,
+    autosave: &mut Autosave,
+    prompt: impl FnOnce() -> T + Send + 'static,
+) -> T {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let _ = tx.send(prompt());
+    });
+
+    loop {
+        // Sem intervalo espera a resposta sem limite
+        let timeout = autosave
+            .interval
+            .map_or(Duration::MAX, |i| i.saturating_sub(autosave.last.elapsed()));
+
+        match rx.recv_timeout(timeout) {
+            Ok(answer) => return answer,
+            Err(RecvTimeoutError::Timeout) => autosave.save(table),
+            Err(RecvTimeoutError::Disconnected) => panic!("o prompt terminou sem resposta"),
+        }
+    }
+}
+
+/// Tabela com o indice secundario (text, nseq) em "alt2" e o de texto em "text".
+fn new_table(gd: u8, bs: u32, hf: u8, md: u8, mt: f32) -> extendible_hash::Result<Table> {
+    let mut h1 = HashAlt1::with_hash_function(gd, bs, hash_function::from_id(hf).unwrap())?;
+    let mut h2 = HashAlt2::with_hash_function(gd, bs, hash_function::from_id(hf).unwrap())?;
+    let mut ht = HashText::with_hash_function(gd, bs, hash_function::from_id(hf).unwrap())?;
+
+    h1.set_max_global_depth(md)?;
+    h2.set_max_global_depth(md)?;
+    ht.set_max_global_depth(md)?;
+
+    h1.set_merge_threshold(mt)?;
+    h2.set_merge_threshold(mt)?;
+    ht.set_merge_threshold(mt)?;
+
+    let mut table = Table::new(h1);
+    table.register("alt2", Box::new(h2))?;
+    table.register("text", Box::new(ht))?;
+
+    Ok(table)
+}
+
+/// Indice criado pelo menu. As funcoes de chave nao sao salvas, entao o
+/// indice e registrado de novo pelo nome ao carregar.
+fn preset(name: &str) -> extendible_hash::Result<Box<dyn SecondaryIndex>> {
+    Ok(match name {
+        "prefixo" => Box::new(KeyIndex::new(1, 4, |r: &Record| {
+            r.text.chars().take(3).collect::<String>()
+        })?),
+        "tamanho" => Box::new(KeyIndex::new(1, 4, |r: &Record| r.text.len())?),
+        _ => Box::new(KeyIndex::new(1, 4, |r: &Record| r.nseq % 10)?),
+    })
+}
+
+fn load() -> extendible_hash::Result<Table> {
+    let mut table = new_table(
+        1,
+        4,
+        hash_function::LOW_BITS_ID,
+        DEFAULT_MAX_GLOBAL_DEPTH,
+        1.0,
+    )?;
+
+    if let Ok(names) = Table::saved_indexes("hash") {
+        for name in names.iter().filter(|n| PRESETS.contains(&n.as_str())) {
+            table.register(name, preset(name)?)?;
+        }
+    }
+
+    // Refaz o que ficou no log se a sessao anterior nao terminou
+    table.recover("hash", SYNC)?;
+
+    Ok(table)
+}
+
+fn save_quit(table: &mut Table) -> extendible_hash::Result<()> {
+    table.sync()
+}
+
+fn rand_hash_values(table: &mut Table, n: usize) -> extendible_hash::Result<()> {
+    let random_nseq = unique_random_numbers(0, n as i32);
+
+    for &nseq in random_nseq.iter().take(n) {
+        let text = random_string(95);
+
+        table.insert(Record { nseq, text })?;
+    }
+
+    Ok(())
+}
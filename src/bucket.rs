@@ -0,0 +1,623 @@
+use std::{fmt::Display, io::Read};
+
+use crate::{
+    error::{Error, Result},
+    extendible_hash::Rid,
+    reader::Reader,
+    traits::{Encode, Keyed},
+};
+
+/// | 4B id | 1B ld | 4B size | 4B overflow | 4B #slots | 4B free |
+pub const PAGE_HEADER: usize = 21;
+
+/// Slot directory entry: | 8B rid | 4B offset | 4B len | 1B spilled |
+pub const SLOT_SIZE: usize = 17;
+
+/// Left in the page by a record moved to overflow pages: | 4B len | 4B first page |
+const STUB_SIZE: usize = 8;
+
+/// Header of an overflow page: | 4B next page | 4B used |
+const SPILL_HEADER: usize = 8;
+
+/// Offsets inside a page are `u32`.
+pub const MAX_PAGE_SIZE: usize = u32::MAX as usize;
+
+/// Room for the header and one spilled record.
+pub const MIN_PAGE_SIZE: usize = PAGE_HEADER + SLOT_SIZE + STUB_SIZE;
+
+/// Where the overflow pages of records too large for their bucket live.
+pub trait SpillPages {
+    /// Stores a page, returning its id.
+    fn write_page(&mut self, page: Vec<u8>) -> Result<u32>;
+
+    /// The page with this id, `None` if there is none.
+    fn read_page(&mut self, id: u32) -> Result<Option<Vec<u8>>>;
+}
+
+/// Overflow pages of a whole table kept in memory, written after its header.
+impl SpillPages for Vec<Vec<u8>> {
+    fn write_page(&mut self, page: Vec<u8>) -> Result<u32> {
+        self.push(page);
+        Ok((self.len() - 1) as u32)
+    }
+
+    fn read_page(&mut self, id: u32) -> Result<Option<Vec<u8>>> {
+        Ok(self.get(id as usize).cloned())
+    }
+}
+
+/// Page that holds `bucket_size` records of `record_size` bytes.
+pub fn page_size_for(bucket_size: u32, record_size: usize) -> usize {
+    (PAGE_HEADER + bucket_size as usize * (SLOT_SIZE + record_size))
+        .clamp(MIN_PAGE_SIZE, MAX_PAGE_SIZE)
+}
+
+/// Letters shown for bucket `id`: A..Z, AA..ZZ, AAA... Only for display,
+/// files and rids refer to buckets by id.
+pub fn label(id: u32) -> String {
+    let mut n = id as u64 + 1;
+    let mut letters = Vec::new();
+
+    while n > 0 {
+        n -= 1;
+        letters.push(b'A' + (n % 26) as u8);
+        n /= 26;
+    }
+
+    letters.reverse();
+
+    String::from_utf8(letters).unwrap()
+}
+
+/// Reads a rid or a record count, saved as `u64`.
+pub(crate) fn read_rid<R: Read>(r: &mut Reader<R>) -> Result<Rid> {
+    // Pode nao caber no usize desta plataforma
+    let n = r.read_u64()?;
+
+    Rid::try_from(n).map_err(|_| r.corrupt(format!("{n} grande demais")))
+}
+
+/// Records longer than this don't fit in an empty page and go to overflow pages.
+fn max_inline(page_size: usize) -> usize {
+    page_size - PAGE_HEADER - SLOT_SIZE
+}
+
+/// Page bytes taken by a record of `len` bytes, slot included.
+fn cost(len: usize, page_size: usize) -> usize {
+    SLOT_SIZE
+        + if len > max_inline(page_size) {
+            STUB_SIZE
+        } else {
+            len
+        }
+}
+
+/// A slotted page: records are kept decoded in `data`, and written after a
+/// slot directory with the offset and length of each one.
+#[derive(Clone)]
+pub struct Bucket<V> {
+    /// Index of the bucket in its table, or page id in a `DiskHash`.
+    pub(crate) id: u32,
+    pub(crate) local_depth: u8,
+    pub(crate) data: Vec<V>,
+    /// Rid of the record in each slot of `data`.
+    pub(crate) ids: Vec<Rid>,
+    /// Records of nominal size the page was planned for, drawn by `Display`.
+    pub(crate) size: u32,
+    pub(crate) page_size: usize,
+    pub(crate) overflow: Option<usize>,
+}
+
+impl<V> Bucket<V> {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Letters drawn for the bucket, from its id.
+    pub fn label(&self) -> String {
+        label(self.id)
+    }
+
+    pub fn local_depth(&self) -> u8 {
+        self.local_depth
+    }
+
+    pub fn records(&self) -> &[V] {
+        &self.data
+    }
+
+    pub fn rids(&self) -> &[Rid] {
+        &self.ids
+    }
+
+    /// Number of nominal size records the page was planned for. Shorter
+    /// records leave room for more.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Index of the next bucket in the overflow chain.
+    pub fn overflow(&self) -> Option<usize> {
+        self.overflow
+    }
+}
+
+impl<V: Keyed> Bucket<V> {
+    pub fn new(id: u32, local_depth: u8, size: u32, page_size: usize) -> Self {
+        Bucket {
+            id,
+            local_depth,
+            data: Vec::with_capacity(size as usize),
+            ids: Vec::with_capacity(size as usize),
+            size,
+            page_size,
+            overflow: None,
+        }
+    }
+
+    pub fn search(&self, key: &V::Key) -> Option<usize> {
+        self.data.iter().position(|v| v.key() == key)
+    }
+}
+
+impl<V: Keyed + Encode> Bucket<V> {
+    /// Bytes taken by the slots and records, header not included.
+    pub fn used(&self, layout: &V::Layout) -> usize {
+        self.data
+            .iter()
+            .map(|v| cost(v.encoded_len(layout), self.page_size))
+            .sum()
+    }
+
+    pub fn fits(&self, v: &V, layout: &V::Layout) -> bool {
+        PAGE_HEADER + self.used(layout) + cost(v.encoded_len(layout), self.page_size)
+            <= self.page_size
+    }
+
+    pub fn insert(&mut self, rid: Rid, v: V, layout: &V::Layout) -> bool {
+        if !self.fits(&v, layout) {
+            return false;
+        }
+
+        self.data.push(v);
+        self.ids.push(rid);
+
+        true
+    }
+}
+
+impl<V: Encode> Bucket<V> {
+    /// Writes the page, `page_size` bytes. Records too long for the page go
+    /// to `spill` as a chain of overflow pages.
+    pub fn serialize<S: SpillPages>(&self, layout: &V::Layout, spill: &mut S) -> Result<Vec<u8>> {
+        // | 4B id | 1B ld | 4B size | 4B overflow | 4B #slots | 4B free | slot1 |...| livre | ...| V2 | V1 |
+
+        let mut records = Vec::with_capacity(self.data.len());
+
+        for v in &self.data {
+            let mut buf = Vec::new();
+            v.encode(layout, &mut buf)?;
+            records.push(buf);
+        }
+
+        let mut spilled: Vec<bool> = records
+            .iter()
+            .map(|r| r.len() > max_inline(self.page_size))
+            .collect();
+
+        // Registros que cresceram depois de inseridos: os maiores vao para
+        // paginas de overflow ate a pagina caber
+        loop {
+            let used: usize = records
+                .iter()
+                .zip(&spilled)
+                .map(|(r, s)| SLOT_SIZE + if *s { STUB_SIZE } else { r.len() })
+                .sum();
+
+            if PAGE_HEADER + used <= self.page_size {
+                break;
+            }
+
+            let largest = (0..records.len())
+                .filter(|i| !spilled[*i] && records[*i].len() > STUB_SIZE)
+                .max_by_key(|i| records[*i].len());
+
+            match largest {
+                Some(i) => spilled[i] = true,
+                None => {
+                    return Err(Error::RecordTooLarge {
+                        size: PAGE_HEADER + used,
+                        max: self.page_size,
+                    })
+                }
+            }
+        }
+
+        let mut encoded = vec![0; self.page_size];
+
+        // Id
+        encoded[0..4].copy_from_slice(&self.id.to_be_bytes());
+
+        // Local Depth
+        encoded[4] = self.local_depth;
+
+        // Size
+        encoded[5..9].copy_from_slice(&self.size.to_be_bytes());
+
+        // Overflow (u32::MAX se nao tem)
+        let overflow = self.overflow.map_or(u32::MAX, |o| o as u32);
+        encoded[9..13].copy_from_slice(&overflow.to_be_bytes());
+
+        // #slots
+        encoded[13..17].copy_from_slice(&(records.len() as u32).to_be_bytes());
+
+        // Registros a partir do fim da pagina
+        let mut free = self.page_size;
+
+        for (i, (record, rid)) in records.iter().zip(&self.ids).enumerate() {
+            let bytes = if spilled[i] {
+                let first = write_spill(record, spill, self.page_size)?;
+                let mut stub = (record.len() as u32).to_be_bytes().to_vec();
+                stub.extend_from_slice(&first.to_be_bytes());
+                stub
+            } else {
+                record.clone()
+            };
+
+            free -= bytes.len();
+            encoded[free..free + bytes.len()].copy_from_slice(&bytes);
+
+            let slot = PAGE_HEADER + i * SLOT_SIZE;
+            encoded[slot..slot + 8].copy_from_slice(&(*rid as u64).to_be_bytes());
+            encoded[slot + 8..slot + 12].copy_from_slice(&(free as u32).to_be_bytes());
+            encoded[slot + 12..slot + 16].copy_from_slice(&(bytes.len() as u32).to_be_bytes());
+            encoded[slot + 16] = spilled[i] as u8;
+        }
+
+        // Free
+        encoded[17..21].copy_from_slice(&(free as u32).to_be_bytes());
+
+        Ok(encoded)
+    }
+
+    /// Reads the page of bucket `id` written by `serialize`, taking spilled
+    /// records from `spill`, the overflow pages of the same file.
+    pub fn deserialize<R: Read, S: SpillPages>(
+        id: u32,
+        layout: &V::Layout,
+        page_size: usize,
+        spill: &mut S,
+        r: &mut Reader<R>,
+    ) -> Result<Self> {
+        let start = r.offset();
+        let mut page = vec![0; page_size];
+        r.read_exact(&mut page)?;
+
+        let mut p = Reader::at(&page[..], start);
+
+        // Id
+        if p.read_u32()? != id {
+            return Err(p.corrupt(format!("pagina de outro balde no lugar do {id}")));
+        }
+
+        // Local Depth
+        let local_depth = p.read_u8()?;
+
+        // Bucket size
+        let size = p.read_u32()?;
+
+        // Overflow (u32::MAX se nao tem)
+        let overflow = p.read_u32()?;
+        let overflow = (overflow != u32::MAX).then_some(overflow as usize);
+
+        // Slots
+        let n = p.read_u32()? as usize;
+        let free = p.read_u32()? as usize;
+
+        if PAGE_HEADER + n * SLOT_SIZE > free || free > page_size {
+            return Err(p.corrupt(format!("{n} slots e espaco livre em {free}")));
+        }
+
+        let mut data: Vec<V> = Vec::with_capacity(n);
+        let mut ids: Vec<Rid> = Vec::with_capacity(n);
+
+        for _ in 0..n {
+            let rid = read_rid(&mut p)?;
+            let offset = p.read_u32()? as usize;
+            let len = p.read_u32()? as usize;
+            let spilled = p.read_u8()? != 0;
+
+            if offset < free || offset + len > page_size || (spilled && len != STUB_SIZE) {
+                return Err(p.corrupt(format!("slot do rid {rid} fora da pagina")));
+            }
+
+            let at = start + offset as u64;
+            let bytes = &page[offset..offset + len];
+
+            let v = if spilled {
+                let total = u32::from_be_bytes(bytes[..4].try_into().unwrap()) as usize;
+                let first = u32::from_be_bytes(bytes[4..].try_into().unwrap());
+                let record = read_spill(spill, first, total)?.ok_or(Error::Corrupt {
+                    offset: at,
+                    reason: format!("paginas de overflow do rid {rid} invalidas"),
+                })?;
+
+                V::decode(layout, &mut Reader::at(&record[..], at), total)?
+            } else {
+                V::decode(layout, &mut Reader::at(bytes, at), len)?
+            };
+
+            data.push(v);
+            ids.push(rid);
+        }
+
+        Ok(Bucket {
+            id,
+            local_depth,
+            data,
+            ids,
+            size,
+            page_size,
+            overflow,
+        })
+    }
+}
+
+/// Writes `record` as a chain of overflow pages, returning the first one.
+/// The chain is written from the end, so every page knows the next id.
+fn write_spill<S: SpillPages>(record: &[u8], spill: &mut S, page_size: usize) -> Result<u32> {
+    let mut next = u32::MAX;
+
+    for chunk in record.chunks(page_size - SPILL_HEADER).rev() {
+        let mut page = Vec::with_capacity(page_size);
+        page.extend_from_slice(&next.to_be_bytes());
+        page.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+        page.extend_from_slice(chunk);
+        page.resize(page_size, 0);
+
+        next = spill.write_page(page)?;
+    }
+
+    Ok(next)
+}
+
+/// Follows the overflow pages from `first`. `None` if the chain is broken.
+fn read_spill<S: SpillPages>(spill: &mut S, first: u32, len: usize) -> Result<Option<Vec<u8>>> {
+    let mut record = Vec::with_capacity(len);
+    let mut page = first;
+
+    while page != u32::MAX && record.len() < len {
+        let Some(p) = spill.read_page(page)? else {
+            return Ok(None);
+        };
+
+        let used = match p.get(4..SPILL_HEADER) {
+            Some(used) => u32::from_be_bytes(used.try_into().unwrap()) as usize,
+            None => return Ok(None),
+        };
+
+        // Pagina vazia numa cadeia so aparece se o arquivo estiver corrompido
+        if used == 0 {
+            return Ok(None);
+        }
+
+        match p.get(SPILL_HEADER..SPILL_HEADER + used) {
+            Some(chunk) => record.extend_from_slice(chunk),
+            None => return Ok(None),
+        }
+
+        page = u32::from_be_bytes(p[..4].try_into().unwrap());
+    }
+
+    Ok((record.len() == len).then_some(record))
+}
+
+impl<V: Keyed> Display for Bucket<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut s = String::new();
+
+        // Local depth
+        let depth_square = format!("+---+\n|{: ^3}|\n", self.local_depth);
+        s.push_str(&depth_square);
+
+        // Bucket (registros curtos podem passar do tamanho planejado)
+        let cells = (self.size as usize).max(self.data.len());
+        let sep = format!("+{}", "---+".repeat(cells));
+        s.push_str(format!("{sep}\n|").as_str());
+
+        for i in 0..cells {
+            if let Some(d) = self.data.get(i) {
+                s.push_str(format!("{: ^3}|", d.label()).as_str())
+            } else {
+                s.push_str("   |")
+            }
+        }
+
+        s.push_str(format!(" {}", self.label()).as_str());
+
+        s.push_str(format!("\n{}", sep.as_str()).as_str());
+
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::record::Record;
+
+    #[test]
+    fn test_bucket_display_size_4() {
+        let b: Bucket<Record> = Bucket::new(0, 2, 4, page_size_for(4, 100));
+
+        println!("{b}");
+
+        assert_eq!(
+            format!("{b}"),
+            "+---+\n| 2 |\n+---+---+---+---+\n|   |   |   |   | A\n+---+---+---+---+"
+        );
+    }
+
+    #[test]
+    fn test_bucket_display_size_8() {
+        let b: Bucket<Record> = Bucket::new(0, 2, 8, page_size_for(8, 100));
+
+        println!("{b}");
+
+        assert_eq!(
+            format!("{b}"),
+            "+---+\n| 2 |\n+---+---+---+---+---+---+---+---+\n|   |   |   |   |   |   |   |   | A\n+---+---+---+---+---+---+---+---+"
+        );
+    }
+
+    #[test]
+    fn test_bucket_display_size_16() {
+        let b: Bucket<Record> = Bucket::new(0, 2, 16, page_size_for(16, 100));
+
+        println!("{b}");
+
+        assert_eq!(
+            format!("{b}"),
+            "+---+\n| 2 |\n+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+\n|   |   |   |   |   |   |   |   |   |   |   |   |   |   |   |   | A\n+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+"
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        let b1 = Bucket {
+            id: 0,
+            local_depth: 2,
+            data: vec![
+                (Record{nseq: 0, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}),
+                (Record{nseq: 1, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}),
+                (Record{nseq: 2, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}),
+                (Record{nseq: 3, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}),
+            ],
+            ids: vec![0, 1, 2, 3],
+            size: 4,
+            page_size: page_size_for(4, 100),
+            overflow: None,
+        };
+
+        let mut spill: Vec<Vec<u8>> = Vec::new();
+        let encoded1 = b1.serialize(&(), &mut spill).unwrap();
+
+        assert_eq!(encoded1.len(), 21 + 4 * (17 + 100));
+        assert!(spill.is_empty());
+
+        let b2: Bucket<Record> = Bucket::deserialize(
+            0,
+            &(),
+            b1.page_size,
+            &mut spill,
+            &mut Reader::new(&encoded1[..]),
+        )
+        .unwrap();
+
+        assert_eq!(b2.id, b1.id);
+        assert_eq!(b2.local_depth, b1.local_depth);
+        assert_eq!(b2.data, b1.data);
+        assert_eq!(b2.ids, b1.ids);
+    }
+
+    #[test]
+    fn test_capacity_in_bytes() {
+        let mut b: Bucket<Record> = Bucket::new(0, 0, 2, page_size_for(2, 100));
+        let short = |nseq| Record {
+            nseq,
+            text: "abc".to_string(),
+        };
+
+        // Registros curtos ocupam menos que o tamanho nominal
+        for nseq in 0..9 {
+            assert!(b.insert(nseq as Rid, short(nseq), &()));
+        }
+
+        assert_eq!(b.used(&()), 9 * (17 + 7));
+        assert!(!b.insert(
+            10,
+            Record {
+                nseq: 10,
+                text: "a".repeat(100),
+            },
+            &()
+        ));
+        assert!(format!("{b}").contains("| 8 |"));
+    }
+
+    #[test]
+    fn test_spill() {
+        let page_size = page_size_for(2, 100);
+        let mut b: Bucket<Record> = Bucket::new(1, 1, 2, page_size);
+        let big = Record {
+            nseq: 1,
+            text: "x".repeat(1000),
+        };
+
+        assert!(b.insert(7, big.clone(), &()));
+        assert!(b.insert(
+            8,
+            Record {
+                nseq: 2,
+                text: "y".repeat(100),
+            },
+            &()
+        ));
+
+        // Cresce depois de inserido: vai para overflow ao salvar
+        b.data[1].text.push_str(&"y".repeat(150));
+
+        let mut spill: Vec<Vec<u8>> = Vec::new();
+        let page = b.serialize(&(), &mut spill).unwrap();
+
+        assert_eq!(page.len(), page_size);
+        assert_eq!(spill.len(), 5 + 2);
+
+        let b2: Bucket<Record> =
+            Bucket::deserialize(1, &(), page_size, &mut spill, &mut Reader::new(&page[..]))
+                .unwrap();
+
+        assert_eq!(b2.data, b.data);
+        assert_eq!(b2.ids, vec![7, 8]);
+
+        // Pagina de outro balde
+        assert!(matches!(
+            Bucket::<Record>::deserialize(
+                2,
+                &(),
+                page_size,
+                &mut spill,
+                &mut Reader::new(&page[..])
+            ),
+            Err(Error::Corrupt { .. })
+        ));
+
+        // Cadeia de overflow cortada
+        spill.truncate(1);
+
+        assert!(matches!(
+            Bucket::<Record>::deserialize(
+                1,
+                &(),
+                page_size,
+                &mut spill,
+                &mut Reader::new(&page[..])
+            ),
+            Err(Error::Corrupt { .. })
+        ));
+    }
+
+    #[test]
+    fn test_label() {
+        assert_eq!(label(0), "A");
+        assert_eq!(label(25), "Z");
+        assert_eq!(label(26), "AA");
+        assert_eq!(label(701), "ZZ");
+        assert_eq!(label(702), "AAA");
+        assert_eq!(label(u32::MAX), "MWLQKWV");
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::error::{Error, Result};
+
+/// Where the pages of a `BufferPool` are read from and written back to.
+pub trait PageFile {
+    fn page_size(&self) -> usize;
+
+    /// Fills `buf` with page `id`.
+    fn read_page(&mut self, id: u32, buf: &mut [u8]) -> Result<()>;
+
+    fn write_page(&mut self, id: u32, buf: &[u8]) -> Result<()>;
+}
+
+/// Pages kept in memory by vector index, for tests.
+impl PageFile for Vec<Vec<u8>> {
+    fn page_size(&self) -> usize {
+        self.first().map_or(0, |p| p.len())
+    }
+
+    fn read_page(&mut self, id: u32, buf: &mut [u8]) -> Result<()> {
+        buf.copy_from_slice(&self[id as usize]);
+        Ok(())
+    }
+
+    fn write_page(&mut self, id: u32, buf: &[u8]) -> Result<()> {
+        self[id as usize].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// Which unpinned frame is reused when the pool is full.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    /// Least recently used.
+    Lru,
+    /// Second chance: a hand sweeps the frames clearing reference bits.
+    Clock,
+    /// Largest distance to the k-th most recent access. Pages accessed fewer
+    /// than k times go first, least recently used among them.
+    LruK(usize),
+}
+
+/// Index of a frame in the pool.
+pub type FrameId = usize;
+
+struct Frame {
+    page: Option<u32>,
+    data: Vec<u8>,
+    pins: u32,
+    dirty: bool,
+    referenced: bool,
+    /// Last accesses, most recent at the back. At most k for `LruK`.
+    history: VecDeque<u64>,
+}
+
+impl Frame {
+    fn new(page_size: usize) -> Self {
+        Frame {
+            page: None,
+            data: vec![0; page_size],
+            pins: 0,
+            dirty: false,
+            referenced: false,
+            history: VecDeque::new(),
+        }
+    }
+}
+
+/// Fixed number of page frames in front of a `PageFile`. A page is pinned
+/// while in use and can't be evicted; dirty pages are written back when
+/// evicted or flushed.
+pub struct BufferPool<F> {
+    file: F,
+    frames: Vec<Frame>,
+    pages: HashMap<u32, FrameId>,
+    policy: Policy,
+    hand: usize,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl<F: PageFile> BufferPool<F> {
+    pub fn new(file: F, frames: usize, policy: Policy) -> Self {
+        let page_size = file.page_size();
+
+        BufferPool {
+            file,
+            frames: (0..frames.max(1)).map(|_| Frame::new(page_size)).collect(),
+            pages: HashMap::new(),
+            policy,
+            hand: 0,
+            clock: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn file(&self) -> &F {
+        &self.file
+    }
+
+    pub fn file_mut(&mut self) -> &mut F {
+        &mut self.file
+    }
+
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Pins found in the pool.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Pins that had to read the page.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Pages in the pool that were changed and not yet written.
+    pub fn dirty(&self) -> usize {
+        self.frames.iter().filter(|f| f.dirty).count()
+    }
+
+    /// Pins page `id`, reading it if it is not in the pool.
+    pub fn pin(&mut self, id: u32) -> Result<FrameId> {
+        self.fetch(id, true)
+    }
+
+    /// Pins page `id` without reading it, for a page that will be
+    /// overwritten whole. The frame keeps its old bytes.
+    pub fn pin_new(&mut self, id: u32) -> Result<FrameId> {
+        self.fetch(id, false)
+    }
+
+    fn fetch(&mut self, id: u32, read: bool) -> Result<FrameId> {
+        self.clock += 1;
+
+        let f = match self.pages.get(&id) {
+            Some(&f) => {
+                self.hits += 1;
+                f
+            }
+            None => {
+                let f = self.victim()?;
+                self.evict(f)?;
+
+                if read {
+                    self.file.read_page(id, &mut self.frames[f].data)?;
+                }
+
+                self.misses += 1;
+
+                self.frames[f].page = Some(id);
+                self.pages.insert(id, f);
+                f
+            }
+        };
+
+        let k = match self.policy {
+            Policy::LruK(k) => k.max(1),
+            _ => 1,
+        };
+        let frame = &mut self.frames[f];
+
+        frame.pins += 1;
+        frame.referenced = true;
+        frame.history.push_back(self.clock);
+
+        if frame.history.len() > k {
+            frame.history.pop_front();
+        }
+
+        Ok(f)
+    }
+
+    /// Releases a pin. `dirty` marks the page to be written back.
+    pub fn unpin(&mut self, f: FrameId, dirty: bool) {
+        let frame = &mut self.frames[f];
+
+        assert!(frame.pins > 0, "unpin of a frame that is not pinned");
+
+        frame.pins -= 1;
+        frame.dirty |= dirty;
+    }
+
+    pub fn page(&self, f: FrameId) -> &[u8] {
+        &self.frames[f].data
+    }
+
+    /// The page of a pinned frame, for changing it. Unpin it as dirty.
+    pub fn page_mut(&mut self, f: FrameId) -> &mut [u8] {
+        &mut self.frames[f].data
+    }
+
+    /// Writes back every dirty page.
+    pub fn flush(&mut self) -> Result<()> {
+        for frame in self.frames.iter_mut() {
+            if let (Some(id), true) = (frame.page, frame.dirty) {
+                self.file.write_page(id, &frame.data)?;
+                frame.dirty = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes back the dirty pages and empties the pool, which now has
+    /// `frames` frames. Fails if a page is pinned.
+    pub fn reset(&mut self, frames: usize, policy: Policy) -> Result<()> {
+        if self.frames.iter().any(|f| f.pins > 0) {
+            return Err(Error::BufferFull(self.frames.len()));
+        }
+
+        self.flush()?;
+
+        let page_size = self.file.page_size();
+
+        self.frames = (0..frames.max(1)).map(|_| Frame::new(page_size)).collect();
+        self.pages.clear();
+        self.policy = policy;
+        self.hand = 0;
+
+        Ok(())
+    }
+
+    /// Writes back the frame if it is dirty and empties it.
+    fn evict(&mut self, f: FrameId) -> Result<()> {
+        let frame = &mut self.frames[f];
+
+        if let Some(id) = frame.page {
+            if frame.dirty {
+                self.file.write_page(id, &frame.data)?;
+            }
+
+            self.pages.remove(&id);
+        }
+
+        frame.page = None;
+        frame.dirty = false;
+        frame.referenced = false;
+        frame.history.clear();
+
+        Ok(())
+    }
+
+    fn victim(&mut self) -> Result<FrameId> {
+        // Frame vazio primeiro
+        if let Some(f) = self.frames.iter().position(|f| f.page.is_none()) {
+            return Ok(f);
+        }
+
+        let unpinned = self.frames.iter().enumerate().filter(|(_, f)| f.pins == 0);
+
+        let victim = match self.policy {
+            Policy::Lru => unpinned
+                .min_by_key(|(_, f)| f.history.back().copied())
+                .map(|(i, _)| i),
+            Policy::LruK(k) => unpinned
+                // Menos de k acessos tem distancia infinita
+                .min_by_key(|(_, f)| {
+                    let kth = if f.history.len() < k.max(1) {
+                        0
+                    } else {
+                        f.history[0]
+                    };
+
+                    (kth, f.history.back().copied())
+                })
+                .map(|(i, _)| i),
+            Policy::Clock => {
+                let n = self.frames.len();
+                let mut victim = None;
+
+                // Duas voltas bastam: a primeira limpa os bits de referencia
+                for _ in 0..2 * n {
+                    let frame = &mut self.frames[self.hand];
+                    let i = self.hand;
+
+                    self.hand = (self.hand + 1) % n;
+
+                    if frame.pins > 0 {
+                        continue;
+                    }
+
+                    if !frame.referenced {
+                        victim = Some(i);
+                        break;
+                    }
+
+                    frame.referenced = false;
+                }
+
+                victim
+            }
+        };
+
+        victim.ok_or(Error::BufferFull(self.frames.len()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pool(frames: usize, policy: Policy) -> BufferPool<Vec<Vec<u8>>> {
+        let pages = (0..10).map(|i| vec![i; 4]).collect();
+
+        BufferPool::new(pages, frames, policy)
+    }
+
+    /// Pins and unpins each page, returning the resident pages.
+    fn access(pool: &mut BufferPool<Vec<Vec<u8>>>, ids: &[u32]) -> Vec<u32> {
+        for id in ids {
+            let f = pool.pin(*id).unwrap();
+            pool.unpin(f, false);
+        }
+
+        let mut resident: Vec<u32> = pool.pages.keys().copied().collect();
+        resident.sort();
+        resident
+    }
+
+    #[test]
+    fn test_pin_and_write_back() {
+        let mut pool = pool(2, Policy::Lru);
+
+        let f = pool.pin(3).unwrap();
+        assert_eq!(pool.page(f), &[3; 4]);
+
+        pool.page_mut(f).copy_from_slice(&[9; 4]);
+        pool.unpin(f, true);
+        assert_eq!(pool.dirty(), 1);
+
+        // Com as paginas 3 e 4 fixadas nao ha frame livre
+        let f3 = pool.pin(3).unwrap();
+        let f4 = pool.pin(4).unwrap();
+        assert!(matches!(pool.pin(5), Err(Error::BufferFull(2))));
+
+        pool.unpin(f3, false);
+        pool.unpin(f4, false);
+
+        // A pagina 3 e gravada ao sair do pool
+        pool.pin(5).unwrap();
+        assert_eq!(pool.file()[3], [9; 4]);
+        assert_eq!((pool.hits(), pool.misses()), (1, 3));
+    }
+
+    #[test]
+    fn test_policies() {
+        assert_eq!(
+            access(&mut pool(3, Policy::Lru), &[1, 2, 3, 1, 4]),
+            [1, 3, 4]
+        );
+
+        // O clock tira 1 mesmo recem acessada: a volta limpa todos os bits
+        assert_eq!(
+            access(&mut pool(3, Policy::Clock), &[1, 2, 3, 1, 4]),
+            [2, 3, 4]
+        );
+        assert_eq!(
+            access(&mut pool(3, Policy::Clock), &[1, 2, 3, 4, 1]),
+            [1, 3, 4]
+        );
+
+        // Uma varredura nao tira paginas acessadas duas vezes
+        assert_eq!(
+            access(&mut pool(3, Policy::LruK(2)), &[1, 1, 2, 2, 3, 4, 5]),
+            [1, 2, 5]
+        );
+        assert_eq!(
+            access(&mut pool(3, Policy::Lru), &[1, 1, 2, 2, 3, 4, 5]),
+            [3, 4, 5]
+        );
+    }
+}
@@ -0,0 +1,1361 @@
+use std::{
+    fmt::Debug,
+    fs::{File, OpenOptions},
+    hash::{Hash, Hasher},
+    io::{BufReader, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    path::Path,
+};
+
+use crate::{
+    bucket::{self, Bucket, SpillPages, PAGE_HEADER},
+    buffer_pool::{BufferPool, PageFile, Policy},
+    crc32::crc32,
+    error::{Error, Result},
+    extendible_hash::{
+        check_merge_threshold, ExtendibleHash, Rid, DEFAULT_MAX_GLOBAL_DEPTH, MAX_GLOBAL_DEPTH,
+    },
+    format,
+    hash_function::{self, HashFunction, LowBits},
+    reader::Reader,
+    traits::{Encode, Keyed},
+};
+
+/// No page: end of the free list.
+const NONE: u32 = u32::MAX;
+
+/// Frames of the buffer pool of a new `DiskHash`.
+pub const DEFAULT_FRAMES: usize = 64;
+
+/// The data pages of a `DiskHash` file, behind its buffer pool.
+pub struct DataFile {
+    file: File,
+    page_size: usize,
+    /// Offset of page 0, after the header and the directory.
+    data_start: u64,
+}
+
+impl DataFile {
+    /// Each page is followed by its CRC-32.
+    fn offset(&self, id: u32) -> u64 {
+        self.data_start + id as u64 * (self.page_size as u64 + 4)
+    }
+}
+
+impl PageFile for DataFile {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn read_page(&mut self, id: u32, buf: &mut [u8]) -> Result<()> {
+        let offset = self.offset(id);
+
+        self.file.seek(SeekFrom::Start(offset))?;
+
+        let mut r = Reader::at(&mut self.file, offset);
+        r.read_exact(buf)?;
+        r.verify_checksum(offset, format!("pagina {id}"))
+    }
+
+    fn write_page(&mut self, id: u32, buf: &[u8]) -> Result<()> {
+        let mut page = buf.to_vec();
+        page.extend_from_slice(&crc32(buf).to_be_bytes());
+
+        self.file.seek(SeekFrom::Start(self.offset(id)))?;
+        self.file.write_all(&page)?;
+
+        Ok(())
+    }
+}
+
+/// Pages of a `DiskHash` file, read and written one at a time through the
+/// buffer pool.
+struct Pager {
+    pool: BufferPool<DataFile>,
+    pages: u32,
+    /// First free page. Each free page starts with the id of the next one.
+    free_head: u32,
+    reads: u64,
+    writes: u64,
+    /// Overflow pages of large records read or written since it was cleared.
+    spilled: Vec<u32>,
+}
+
+impl Pager {
+    fn new(file: File, page_size: usize, data_start: u64, pages: u32, free_head: u32) -> Self {
+        let file = DataFile {
+            file,
+            page_size,
+            data_start,
+        };
+
+        Pager {
+            pool: BufferPool::new(file, DEFAULT_FRAMES, Policy::Lru),
+            pages,
+            free_head,
+            reads: 0,
+            writes: 0,
+            spilled: Vec::new(),
+        }
+    }
+
+    fn page_size(&self) -> usize {
+        self.pool.file().page_size
+    }
+
+    fn offset(&self, id: u32) -> u64 {
+        self.pool.file().offset(id)
+    }
+
+    fn read(&mut self, id: u32) -> Result<Vec<u8>> {
+        if id >= self.pages {
+            return Err(Error::Corrupt {
+                offset: self.offset(id),
+                reason: format!("pagina {id} fora do arquivo"),
+            });
+        }
+
+        let f = self.pool.pin(id)?;
+        let page = self.pool.page(f).to_vec();
+
+        self.pool.unpin(f, false);
+        self.reads += 1;
+
+        Ok(page)
+    }
+
+    fn write(&mut self, id: u32, page: &[u8]) -> Result<()> {
+        let f = self.pool.pin_new(id)?;
+
+        self.pool.page_mut(f).copy_from_slice(page);
+        self.pool.unpin(f, true);
+        self.writes += 1;
+
+        Ok(())
+    }
+
+    /// Reuses a free page if there is one.
+    fn alloc(&mut self) -> Result<u32> {
+        if self.free_head != NONE {
+            let id = self.free_head;
+            let page = self.read(id)?;
+
+            self.free_head = u32::from_be_bytes(page[..4].try_into().unwrap());
+
+            return Ok(id);
+        }
+
+        // NONE marca o fim das listas
+        if self.pages == NONE {
+            return Err(Error::TooManyBuckets(self.pages as usize + 1));
+        }
+
+        self.pages += 1;
+
+        Ok(self.pages - 1)
+    }
+
+    fn free(&mut self, id: u32) -> Result<()> {
+        let mut page = vec![0; self.page_size()];
+        page[..4].copy_from_slice(&self.free_head.to_be_bytes());
+
+        self.write(id, &page)?;
+        self.free_head = id;
+
+        Ok(())
+    }
+}
+
+impl SpillPages for Pager {
+    fn write_page(&mut self, page: Vec<u8>) -> Result<u32> {
+        let id = self.alloc()?;
+
+        self.write(id, &page)?;
+        self.spilled.push(id);
+
+        Ok(id)
+    }
+
+    fn read_page(&mut self, id: u32) -> Result<Option<Vec<u8>>> {
+        if id >= self.pages {
+            return Ok(None);
+        }
+
+        self.spilled.push(id);
+
+        self.read(id).map(Some)
+    }
+}
+
+/// A bucket read from its page, with the overflow pages of its large records.
+struct Page<V> {
+    id: u32,
+    bucket: Bucket<V>,
+    spilled: Vec<u32>,
+}
+
+/// Extendible hash kept in a file. Every bucket is a page at a fixed offset
+/// and only the directory is in memory, so `search`, `insert` and `remove`
+/// read and write just the pages of the buckets they touch. Pages go through
+/// a `BufferPool` and reach the file when evicted; the header, the directory
+/// and the cached pages are written by `flush` and when the table is dropped.
+///
+/// `| header | 4B crc | directory (2^directory depth * 4B) | page 0 | crc | page 1 | crc |...|`
+pub struct DiskHash<K, V: Encode> {
+    pager: Pager,
+    global_depth: u8,
+    max_global_depth: u8,
+    /// Depth of the largest directory that fits before the first page.
+    directory_depth: u8,
+    directory: Vec<u32>,
+    /// Records of nominal size a page is planned for.
+    bucket_size: u32,
+    merge_threshold: f32,
+    hash_function: Box<dyn HashFunction>,
+    layout: V::Layout,
+    next_rid: Rid,
+    len: usize,
+    key: PhantomData<K>,
+}
+
+impl<K, V> DiskHash<K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode,
+{
+    /// Creates (or truncates) the file at `path`, with room for a directory
+    /// of depth `DEFAULT_MAX_GLOBAL_DEPTH`.
+    pub fn create<P: AsRef<Path>>(path: P, global_depth: u8, bucket_size: u32) -> Result<Self> {
+        Self::create_with(
+            path,
+            global_depth,
+            DEFAULT_MAX_GLOBAL_DEPTH,
+            bucket_size,
+            Box::new(LowBits),
+            V::Layout::default(),
+        )
+    }
+
+    /// `create` with room for a directory of depth `max_global_depth`, the
+    /// most the directory can grow to, even with `set_max_global_depth`.
+    /// The room is reserved before the first page, 4B per entry. Fails with
+    /// `DepthLimit` if `global_depth` is above `max_global_depth`.
+    pub fn create_with<P: AsRef<Path>>(
+        path: P,
+        global_depth: u8,
+        max_global_depth: u8,
+        bucket_size: u32,
+        hash_function: Box<dyn HashFunction>,
+        layout: V::Layout,
+    ) -> Result<Self> {
+        if max_global_depth > MAX_GLOBAL_DEPTH {
+            return Err(Error::DepthLimit(max_global_depth));
+        }
+
+        if global_depth > max_global_depth {
+            return Err(Error::DepthLimit(global_depth));
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        let page_size = bucket::page_size_for(bucket_size, V::nominal_size(&layout));
+        let n = 1 << global_depth;
+
+        let mut h = DiskHash {
+            pager: Pager::new(file, page_size, 0, n, NONE),
+            global_depth,
+            max_global_depth,
+            directory_depth: max_global_depth,
+            directory: (0..n).collect(),
+            bucket_size,
+            merge_threshold: 1.0,
+            hash_function,
+            layout,
+            next_rid: 0,
+            len: 0,
+            key: PhantomData,
+        };
+
+        h.pager.pool.file_mut().data_start = h.data_start();
+
+        for id in 0..n {
+            let mut page = h.new_page(id, global_depth);
+            h.write_bucket(&mut page)?;
+        }
+
+        h.flush()?;
+
+        Ok(h)
+    }
+
+    /// Opens a file written with one of the built-in hash functions. The
+    /// headerless files of `HashAlt1` from the first releases are upgraded
+    /// first.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read(path, None)
+    }
+
+    /// Opens a file written with a `Custom` hash function, which must be the
+    /// same one it was created with.
+    pub fn open_with_hash_function<P: AsRef<Path>>(
+        path: P,
+        hash_function: Box<dyn HashFunction>,
+    ) -> Result<Self> {
+        Self::read(path, Some(hash_function))
+    }
+
+    fn read<P: AsRef<Path>>(path: P, hash_function: Option<Box<dyn HashFunction>>) -> Result<Self> {
+        let mut file = OpenOptions::new().read(true).write(true).open(&path)?;
+        let mut r = Reader::new(&mut file);
+        let first = r.read_u8()?;
+
+        if V::LEGACY_SIZE > 0 && format::is_legacy(first, format::DISK_MAGIC) {
+            return Self::upgrade_legacy(path.as_ref(), hash_function);
+        }
+
+        // Numero magico, versao e tipo (6B)
+        format::read_header_from(&mut r, first, format::DISK_MAGIC, V::KIND)?;
+
+        // Global depth, max global depth, depth reservada do diretorio (1B cada)
+        let global_depth = r.read_u8()?;
+        let max_global_depth = r.read_u8()?;
+        let directory_depth = r.read_u8()?;
+
+        if directory_depth > MAX_GLOBAL_DEPTH
+            || max_global_depth > directory_depth
+            || global_depth > max_global_depth
+        {
+            return Err(r.corrupt(format!(
+                "global depth {global_depth} com maximo {max_global_depth} e diretorio para {directory_depth}"
+            )));
+        }
+
+        // Hash function (1B)
+        let id = r.read_u8()?;
+
+        let hash_function = match hash_function {
+            Some(h) if h.id() == id => h,
+            Some(h) => {
+                return Err(Error::WrongFormat {
+                    field: "funcao de hash",
+                    expected: h.id().to_string(),
+                    found: id.to_string(),
+                })
+            }
+            None => hash_function::from_id(id)
+                .ok_or_else(|| r.corrupt(format!("funcao de hash {id} desconhecida")))?,
+        };
+
+        // Proximo rid, #registros (8B cada)
+        let next_rid = bucket::read_rid(&mut r)?;
+        let len = bucket::read_rid(&mut r)?;
+
+        // Page size, bucket size (4B cada)
+        let page_size = r.read_u32()? as usize;
+        let bucket_size = r.read_u32()?;
+
+        if page_size < bucket::MIN_PAGE_SIZE {
+            return Err(r.corrupt(format!("pagina de {page_size} bytes")));
+        }
+
+        // #paginas, primeira livre (4B cada)
+        let pages = r.read_u32()?;
+        let free_head = r.read_u32()?;
+
+        if free_head != NONE && free_head >= pages {
+            return Err(r.corrupt(format!("pagina livre {free_head} fora do arquivo")));
+        }
+
+        // Layout
+        let layout = V::read_layout(&mut r)?;
+
+        // CRC do cabecalho e do diretorio (4B cada)
+        r.verify_checksum(0, "cabecalho")?;
+
+        let crc = r.read_u32()?;
+        let start = r.offset();
+        let data_start = start + 4 * (1 << directory_depth);
+
+        // Diretorio
+        let mut directory = Vec::with_capacity(1 << global_depth);
+        r.begin_checksum();
+
+        for _ in 0..1_u64 << global_depth {
+            directory.push(r.read_u32()?);
+        }
+
+        if r.checksum() != crc {
+            return Err(Error::Checksum {
+                offset: start,
+                part: "diretorio".to_string(),
+            });
+        }
+
+        if let Some(i) = directory.iter().position(|d| *d >= pages) {
+            return Err(Error::Corrupt {
+                offset: start + 4 * i as u64,
+                reason: format!("diretorio aponta para a pagina {}", directory[i]),
+            });
+        }
+
+        Ok(DiskHash {
+            pager: Pager::new(file, page_size, data_start, pages, free_head),
+            global_depth,
+            max_global_depth,
+            directory_depth,
+            directory,
+            bucket_size,
+            merge_threshold: 1.0,
+            hash_function,
+            layout,
+            next_rid,
+            len,
+            key: PhantomData,
+        })
+    }
+
+    /// Replaces a headerless file of `format::LEGACY_VERSION`, as the first
+    /// releases saved their tables, with a new file holding its records.
+    fn upgrade_legacy(path: &Path, hash_function: Option<Box<dyn HashFunction>>) -> Result<Self> {
+        let f = File::open(path)?;
+        let table: ExtendibleHash<K, V> = ExtendibleHash::read_from(&mut BufReader::new(f))?;
+        let bucket_size = table.buckets[0].size();
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        let mut h = Self::create_with(
+            &tmp,
+            table.global_depth(),
+            table.max_global_depth(),
+            bucket_size,
+            hash_function.unwrap_or_else(|| Box::new(LowBits)),
+            table.layout().clone(),
+        )?;
+
+        for v in table {
+            h.insert(v)?;
+        }
+
+        h.flush()?;
+        h.pager.pool.file_mut().file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+
+        Ok(h)
+    }
+
+    /// Same as `ExtendibleHash::set_merge_threshold`.
+    pub fn set_merge_threshold(&mut self, threshold: f32) -> Result<()> {
+        self.merge_threshold = check_merge_threshold(threshold)?;
+
+        Ok(())
+    }
+
+    /// Same as `ExtendibleHash::set_max_global_depth`, up to the depth the
+    /// file has room for, given to `create_with`.
+    pub fn set_max_global_depth(&mut self, max_global_depth: u8) -> Result<()> {
+        if max_global_depth > self.directory_depth {
+            return Err(Error::DepthLimit(max_global_depth));
+        }
+
+        self.max_global_depth = max_global_depth.max(self.global_depth);
+
+        Ok(())
+    }
+
+    fn hash(&self, key: &K) -> u64 {
+        let mut hasher = self.hash_function.hasher();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Directory index of `key`: the low `global_depth` bits of its hash.
+    pub fn hash_fun(&self, key: &K) -> usize {
+        (self.hash(key) & ((1 << self.global_depth) - 1)) as usize
+    }
+
+    fn new_page(&self, id: u32, local_depth: u8) -> Page<V> {
+        Page {
+            id,
+            bucket: Bucket::new(id, local_depth, self.bucket_size, self.pager.page_size()),
+            spilled: Vec::new(),
+        }
+    }
+
+    fn read_bucket(&mut self, id: u32) -> Result<Page<V>> {
+        let bytes = self.pager.read(id)?;
+        let offset = self.pager.offset(id);
+
+        self.pager.spilled.clear();
+
+        let bucket = Bucket::deserialize(
+            id,
+            &self.layout,
+            self.pager.page_size(),
+            &mut self.pager,
+            &mut Reader::at(&bytes[..], offset),
+        )?;
+
+        Ok(Page {
+            id,
+            bucket,
+            spilled: std::mem::take(&mut self.pager.spilled),
+        })
+    }
+
+    fn write_bucket(&mut self, page: &mut Page<V>) -> Result<()> {
+        // Os registros grandes sao regravados em paginas novas
+        for id in page.spilled.drain(..) {
+            self.pager.free(id)?;
+        }
+
+        self.pager.spilled.clear();
+
+        let bytes = page.bucket.serialize(&self.layout, &mut self.pager)?;
+        page.spilled = std::mem::take(&mut self.pager.spilled);
+
+        self.pager.write(page.id, &bytes)
+    }
+
+    /// The bucket at `first` and its overflow buckets.
+    fn chain(&mut self, first: u32) -> Result<Vec<Page<V>>> {
+        let mut chain: Vec<Page<V>> = Vec::new();
+        let mut next = Some(first);
+
+        while let Some(id) = next {
+            if chain.len() > self.pager.pages as usize {
+                return Err(Error::Corrupt {
+                    offset: self.pager.offset(first),
+                    reason: format!("cadeia de overflow da pagina {first} em ciclo"),
+                });
+            }
+
+            let page = self.read_bucket(id)?;
+            next = page.bucket.overflow.map(|o| o as u32);
+            chain.push(page);
+        }
+
+        Ok(chain)
+    }
+
+    /// In a table with duplicate keys, returns the first record found.
+    pub fn search(&mut self, key: &K) -> Result<V> {
+        let mut next = Some(self.directory[self.hash_fun(key)]);
+
+        while let Some(id) = next {
+            let mut page = self.read_bucket(id)?;
+
+            if let Some(slot) = page.bucket.search(key) {
+                return Ok(page.bucket.data.swap_remove(slot));
+            }
+
+            next = page.bucket.overflow.map(|o| o as u32);
+        }
+
+        Err(Error::KeyNotFound(format!("{key:?}")))
+    }
+
+    /// Every record with `key`, for tables with duplicate keys.
+    pub fn search_all(&mut self, key: &K) -> Result<Vec<V>> {
+        let chain = self.chain(self.directory[self.hash_fun(key)])?;
+
+        Ok(chain
+            .into_iter()
+            .flat_map(|p| p.bucket.data)
+            .filter(|v| v.key() == key)
+            .collect())
+    }
+
+    pub fn insert(&mut self, value: V) -> Result<Rid> {
+        value.validate(&self.layout)?;
+
+        let h = self.hash_fun(value.key());
+        let chain = self.chain(self.directory[h])?;
+
+        if V::UNIQUE && chain.iter().any(|p| p.bucket.search(value.key()).is_some()) {
+            return Err(Error::DuplicateKey(format!("{:?}", value.key())));
+        }
+
+        let rid = self.next_rid;
+
+        self.place(h, chain, rid, value)?;
+        self.next_rid += 1;
+        self.len += 1;
+
+        Ok(rid)
+    }
+
+    /// Puts `value` in the chain of directory entry `h`, already read.
+    fn place(&mut self, h: usize, mut chain: Vec<Page<V>>, rid: Rid, value: V) -> Result<()> {
+        // Se todos tem o mesmo hash (chaves repetidas) dividir nunca separa
+        let hash = self.hash(value.key());
+        let same_hash = chain
+            .iter()
+            .all(|p| p.bucket.data.iter().all(|v| self.hash(v.key()) == hash));
+
+        if let Some(page) = chain
+            .iter_mut()
+            .find(|p| p.bucket.fits(&value, &self.layout))
+        {
+            page.bucket.insert(rid, value, &self.layout);
+
+            return self.write_bucket(page);
+        }
+
+        let local_depth = chain[0].bucket.local_depth;
+
+        if local_depth < self.max_global_depth && !same_hash {
+            return self.split(h, chain, rid, value);
+        }
+
+        // Nao da para dividir: encadeia um balde de overflow
+        let id = self.pager.alloc()?;
+        let mut page = self.new_page(id, local_depth);
+
+        page.bucket.insert(rid, value, &self.layout);
+        self.write_bucket(&mut page)?;
+
+        let last = chain.last_mut().unwrap();
+        last.bucket.overflow = Some(id as usize);
+
+        self.write_bucket(last)
+    }
+
+    fn split(&mut self, h: usize, chain: Vec<Page<V>>, rid: Rid, value: V) -> Result<()> {
+        let first = chain[0].id;
+        let bit = chain[0].bucket.local_depth;
+
+        // Dobra diretorio se ld = gd
+        if bit == self.global_depth {
+            let n = self.directory.len();
+
+            self.directory.extend_from_within(..n);
+            self.global_depth += 1;
+        }
+
+        // Retira os registros da cadeia e libera os overflows
+        let mut records = Vec::new();
+
+        for (i, page) in chain.into_iter().enumerate() {
+            records.extend(page.bucket.ids.into_iter().zip(page.bucket.data));
+
+            for id in page.spilled {
+                self.pager.free(id)?;
+            }
+
+            if i > 0 {
+                self.pager.free(page.id)?;
+            }
+        }
+
+        let new_id = self.pager.alloc()?;
+
+        // Entradas que apontavam para o balde e tem o novo bit ligado passam
+        // a apontar para o balde novo
+        for (i, d) in self.directory.iter_mut().enumerate() {
+            if *d == first && (i >> bit) & 1 == 1 {
+                *d = new_id;
+            }
+        }
+
+        let mut low = self.new_page(first, bit + 1);
+        let mut high = self.new_page(new_id, bit + 1);
+        let mut rest = Vec::new();
+
+        for (r, v) in records {
+            let page = if self.directory[self.hash_fun(v.key())] == first {
+                &mut low
+            } else {
+                &mut high
+            };
+
+            if page.bucket.fits(&v, &self.layout) {
+                page.bucket.insert(r, v, &self.layout);
+            } else {
+                rest.push((r, v));
+            }
+        }
+
+        // No caso comum a divisao so toca as duas paginas e o diretorio
+        let page = if self.directory[self.hash_fun(value.key())] == first {
+            &mut low
+        } else {
+            &mut high
+        };
+
+        if rest.is_empty() && page.bucket.fits(&value, &self.layout) {
+            page.bucket.insert(rid, value, &self.layout);
+
+            self.write_bucket(&mut low)?;
+            return self.write_bucket(&mut high);
+        }
+
+        self.write_bucket(&mut low)?;
+        self.write_bucket(&mut high)?;
+
+        // Se tudo caiu do mesmo lado o balde divide de novo
+        for (r, v) in rest {
+            let h = self.hash_fun(v.key());
+            let chain = self.chain(self.directory[h])?;
+
+            self.place(h, chain, r, v)?;
+        }
+
+        let h = h & (self.directory.len() - 1);
+        let h = if self.directory[h] == first || self.directory[h] == new_id {
+            self.hash_fun(value.key())
+        } else {
+            h
+        };
+        let chain = self.chain(self.directory[h])?;
+
+        self.place(h, chain, rid, value)
+    }
+
+    /// In a table with duplicate keys, removes the first record found.
+    pub fn remove(&mut self, key: &K) -> Result<V> {
+        let h = self.hash_fun(key);
+        let mut chain = self.chain(self.directory[h])?;
+
+        let (i, slot) = chain
+            .iter()
+            .enumerate()
+            .find_map(|(i, p)| p.bucket.search(key).map(|s| (i, s)))
+            .ok_or_else(|| Error::KeyNotFound(format!("{key:?}")))?;
+
+        let value = chain[i].bucket.data.remove(slot);
+        chain[i].bucket.ids.remove(slot);
+        self.len -= 1;
+
+        if i > 0 && chain[i].bucket.data.is_empty() {
+            // Overflow vazio sai da cadeia
+            let gone = chain.remove(i);
+
+            chain[i - 1].bucket.overflow = gone.bucket.overflow;
+            self.write_bucket(&mut chain[i - 1])?;
+
+            for id in gone.spilled {
+                self.pager.free(id)?;
+            }
+
+            self.pager.free(gone.id)?;
+        } else {
+            self.write_bucket(&mut chain[i])?;
+        }
+
+        if chain.len() == 1 {
+            self.merge(h, chain.pop().unwrap())?;
+        }
+
+        Ok(value)
+    }
+
+    /// Merges `page`, the bucket at `dir_index`, with its buddy while they
+    /// fit under the merge threshold, halving the directory when possible.
+    fn merge(&mut self, mut dir_index: usize, mut page: Page<V>) -> Result<()> {
+        loop {
+            let local_depth = page.bucket.local_depth;
+
+            if local_depth == 0 || page.bucket.overflow.is_some() {
+                return Ok(());
+            }
+
+            // O buddy difere apenas no bit ld - 1
+            let bit = 1 << (local_depth - 1);
+            let buddy_id = self.directory[dir_index ^ bit];
+
+            if buddy_id == page.id {
+                return Ok(());
+            }
+
+            let buddy = self.read_bucket(buddy_id)?;
+
+            if buddy.bucket.local_depth != local_depth || buddy.bucket.overflow.is_some() {
+                return Ok(());
+            }
+
+            let combined = page.bucket.used(&self.layout) + buddy.bucket.used(&self.layout);
+            let limit = (self.pager.page_size() - PAGE_HEADER) as f32 * self.merge_threshold;
+
+            if combined as f32 > limit {
+                return Ok(());
+            }
+
+            // Mantem o balde com bit 0 e libera o outro
+            let (mut keep, gone) = if dir_index & bit == 0 {
+                (page, buddy)
+            } else {
+                (buddy, page)
+            };
+
+            keep.bucket.data.extend(gone.bucket.data);
+            keep.bucket.ids.extend(gone.bucket.ids);
+            keep.bucket.local_depth -= 1;
+
+            for id in gone.spilled {
+                self.pager.free(id)?;
+            }
+
+            self.pager.free(gone.id)?;
+
+            for d in self.directory.iter_mut() {
+                if *d == gone.id {
+                    *d = keep.id;
+                }
+            }
+
+            self.write_bucket(&mut keep)?;
+
+            // Reduz diretorio se nenhum balde tem ld = gd, ou seja, se as
+            // duas metades sao iguais
+            while self.global_depth > 0 {
+                let half = self.directory.len() / 2;
+
+                if self.directory[..half] != self.directory[half..] {
+                    break;
+                }
+
+                self.directory.truncate(half);
+                self.global_depth -= 1;
+            }
+
+            dir_index &= self.directory.len() - 1;
+            page = keep;
+        }
+    }
+
+    /// Reads every page. Meant for tests and small tables.
+    pub fn values(&mut self) -> Result<Vec<V>> {
+        let mut firsts = self.directory.clone();
+        firsts.sort();
+        firsts.dedup();
+
+        let mut values = Vec::with_capacity(self.len);
+
+        for first in firsts {
+            for page in self.chain(first)? {
+                values.extend(page.bucket.data);
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Same checks as `ExtendibleHash::check_invariants`, reading every page.
+    pub fn check_invariants(&mut self) -> std::result::Result<(), String> {
+        let mut firsts = self.directory.clone();
+        firsts.sort();
+        firsts.dedup();
+
+        let mut count = 0;
+
+        for first in firsts {
+            let refs: Vec<usize> = (0..self.directory.len())
+                .filter(|i| self.directory[*i] == first)
+                .collect();
+
+            let chain = self.chain(first).map_err(|e| e.to_string())?;
+            let local_depth = chain[0].bucket.local_depth;
+
+            if local_depth > self.global_depth {
+                return Err(format!(
+                    "Page {first} has local depth {local_depth} > global depth {}",
+                    self.global_depth
+                ));
+            }
+
+            if refs.len() != 1 << (self.global_depth - local_depth) {
+                return Err(format!(
+                    "Page {first} is referenced by {} entries",
+                    refs.len()
+                ));
+            }
+
+            for page in &chain {
+                if page.bucket.local_depth != local_depth {
+                    return Err(format!(
+                        "Overflow page {} has a different local depth than {first}",
+                        page.id
+                    ));
+                }
+
+                for v in &page.bucket.data {
+                    if self.directory[self.hash_fun(v.key())] != first {
+                        return Err(format!(
+                            "Key {} is in page {} but hashes elsewhere",
+                            v.label(),
+                            page.id
+                        ));
+                    }
+                }
+
+                count += page.bucket.data.len();
+            }
+        }
+
+        if count != self.len {
+            return Err(format!("{count} records, header says {}", self.len));
+        }
+
+        Ok(())
+    }
+}
+
+impl<K, V: Encode> DiskHash<K, V> {
+    pub fn global_depth(&self) -> u8 {
+        self.global_depth
+    }
+
+    pub fn directory(&self) -> &[u32] {
+        &self.directory
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.pager.page_size()
+    }
+
+    /// Pages in the file, free ones included.
+    pub fn page_count(&self) -> u32 {
+        self.pager.pages
+    }
+
+    /// Pages read and written since the file was opened, cached or not.
+    pub fn page_io(&self) -> (u64, u64) {
+        (self.pager.reads, self.pager.writes)
+    }
+
+    /// The page cache, for its hit and miss counters.
+    pub fn buffer_pool(&self) -> &BufferPool<DataFile> {
+        &self.pager.pool
+    }
+
+    /// Writes back the cached pages and starts a new cache of `frames`
+    /// pages with the given replacement policy.
+    pub fn set_buffer_pool(&mut self, frames: usize, policy: Policy) -> Result<()> {
+        self.pager.pool.reset(frames, policy)
+    }
+
+    /// Offset of page 0, after the room reserved for the directory.
+    fn data_start(&self) -> u64 {
+        self.header().len() as u64 + 4 + 4 * (1 << self.directory_depth)
+    }
+
+    fn header(&self) -> Vec<u8> {
+        // | 4B magic | 1B versao | 1B tipo | 1B gd | 1B max gd | 1B depth do diretorio | 1B hash |
+        // | 8B next rid | 8B len | 4B page size | 4B bucket size | 4B #paginas | 4B livre |
+        // | layout | 4B crc |
+
+        let mut header = Vec::new();
+
+        format::write_header(format::DISK_MAGIC, V::KIND, &mut header);
+        header.push(self.global_depth);
+        header.push(self.max_global_depth);
+        header.push(self.directory_depth);
+        header.push(self.hash_function.id());
+
+        header.extend_from_slice(&(self.next_rid as u64).to_be_bytes());
+        header.extend_from_slice(&(self.len as u64).to_be_bytes());
+        header.extend_from_slice(&(self.pager.page_size() as u32).to_be_bytes());
+        header.extend_from_slice(&self.bucket_size.to_be_bytes());
+        header.extend_from_slice(&self.pager.pages.to_be_bytes());
+        header.extend_from_slice(&self.pager.free_head.to_be_bytes());
+
+        V::write_layout(&self.layout, &mut header);
+
+        let crc = crc32(&header);
+        header.extend_from_slice(&crc.to_be_bytes());
+
+        header
+    }
+
+    /// Writes the dirty pages of the buffer pool, the header and the directory.
+    pub fn flush(&mut self) -> Result<()> {
+        let mut header = self.header();
+        let directory: Vec<u8> = self
+            .directory
+            .iter()
+            .flat_map(|d| d.to_be_bytes())
+            .collect();
+
+        // | 4B crc | diretorio |
+        header.extend_from_slice(&crc32(&directory).to_be_bytes());
+        header.extend(directory);
+
+        self.pager.pool.flush()?;
+
+        let file = &mut self.pager.pool.file_mut().file;
+
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&header)?;
+        file.flush()?;
+
+        Ok(())
+    }
+}
+
+impl<K, V: Encode> Drop for DiskHash<K, V> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        extendible_hash::{self, ExtendibleHash},
+        hash_text::TextEntry,
+        record::Record,
+        temp_path,
+    };
+
+    fn record(nseq: i32) -> Record {
+        Record {
+            nseq,
+            text: format!("registro {nseq}"),
+        }
+    }
+
+    #[test]
+    fn test_insert_search_remove() {
+        let path = temp_path("disk_basic.bin");
+        let mut h: DiskHash<i32, Record> = DiskHash::create(&path, 1, 4).unwrap();
+
+        // Permutacao de 0..=300
+        let keys: Vec<i32> = (0..=300).map(|i| (i * 7919) % 301).collect();
+
+        for nseq in &keys {
+            h.insert(record(*nseq)).unwrap();
+        }
+
+        assert_eq!(h.len(), 301);
+        assert_eq!(h.check_invariants(), Ok(()));
+        assert!(matches!(h.insert(record(5)), Err(Error::DuplicateKey(_))));
+
+        for nseq in &keys {
+            assert_eq!(h.search(nseq).unwrap(), record(*nseq));
+        }
+
+        for nseq in keys.iter().step_by(2) {
+            assert_eq!(h.remove(nseq).unwrap(), record(*nseq));
+        }
+
+        assert_eq!(h.check_invariants(), Ok(()));
+        assert!(matches!(h.search(&keys[0]), Err(Error::KeyNotFound(_))));
+
+        for nseq in keys.iter().skip(1).step_by(2) {
+            h.remove(nseq).unwrap();
+        }
+
+        assert!(h.is_empty());
+        assert_eq!(h.global_depth(), 0);
+        assert_eq!(h.check_invariants(), Ok(()));
+        assert!(matches!(
+            h.set_merge_threshold(2.0),
+            Err(Error::MergeThreshold(_))
+        ));
+    }
+
+    #[test]
+    fn test_reopen() {
+        let path = temp_path("disk_reopen.bin");
+
+        {
+            let mut h: DiskHash<i32, Record> = DiskHash::create(&path, 2, 2).unwrap();
+
+            for nseq in 0..50 {
+                h.insert(record(nseq)).unwrap();
+            }
+        }
+
+        let mut h: DiskHash<i32, Record> = DiskHash::open(&path).unwrap();
+
+        assert_eq!(h.len(), 50);
+        assert_eq!(h.check_invariants(), Ok(()));
+        assert_eq!(h.search(&42).unwrap(), record(42));
+
+        // Rids nao se repetem depois de reabrir
+        assert_eq!(h.insert(record(50)).unwrap(), 50);
+    }
+
+    #[test]
+    fn test_directory_depth() {
+        let path = temp_path("disk_depth.bin");
+        let big = |nseq| Record {
+            nseq,
+            text: "x".repeat(90),
+        };
+        let mut h: DiskHash<i32, Record> =
+            DiskHash::create_with(&path, 0, 17, 1, Box::new(LowBits), ()).unwrap();
+
+        // Mesmos 16 bits baixos: so separam com o diretorio de depth 17
+        h.insert(big(0)).unwrap();
+        h.insert(big(1 << 16)).unwrap();
+
+        assert_eq!(h.global_depth(), 17);
+        assert!(matches!(
+            h.set_max_global_depth(18),
+            Err(Error::DepthLimit(18))
+        ));
+        drop(h);
+
+        let mut h: DiskHash<i32, Record> = DiskHash::open(&path).unwrap();
+
+        assert_eq!(h.check_invariants(), Ok(()));
+        assert_eq!(h.search(&(1 << 16)).unwrap(), big(1 << 16));
+
+        // Sem espaco reservado o diretorio para em 15
+        let mut h: DiskHash<i32, Record> = DiskHash::create(&path, 0, 1).unwrap();
+
+        h.insert(big(0)).unwrap();
+        h.insert(big(1 << 16)).unwrap();
+
+        assert_eq!(h.global_depth(), 15);
+        assert!(matches!(
+            h.set_max_global_depth(16),
+            Err(Error::DepthLimit(16))
+        ));
+        assert!(matches!(
+            DiskHash::<i32, Record>::create_with(&path, 0, 33, 1, Box::new(LowBits), ()),
+            Err(Error::DepthLimit(33))
+        ));
+        assert!(matches!(
+            DiskHash::<i32, Record>::create(&path, 16, 1),
+            Err(Error::DepthLimit(16))
+        ));
+    }
+
+    #[test]
+    fn test_page_io() {
+        let path = temp_path("disk_io.bin");
+        let mut h: DiskHash<i32, Record> = DiskHash::create(&path, 3, 4).unwrap();
+
+        for nseq in 0..8 {
+            h.insert(record(nseq)).unwrap();
+        }
+
+        // Uma leitura por busca e uma leitura e uma escrita por insercao sem divisao
+        let (reads, writes) = h.page_io();
+        h.search(&3).unwrap();
+        assert_eq!(h.page_io(), (reads + 1, writes));
+
+        h.insert(record(8)).unwrap();
+        assert_eq!(h.page_io(), (reads + 2, writes + 1));
+    }
+
+    #[test]
+    fn test_split_pages() {
+        let path = temp_path("disk_split.bin");
+        let mut h: DiskHash<i32, Record> = DiskHash::create(&path, 0, 2).unwrap();
+        let mut nseq = 0;
+
+        // Insere ate a primeira divisao
+        while h.page_count() == 1 {
+            let (reads, writes) = h.page_io();
+            h.insert(record(nseq)).unwrap();
+            nseq += 1;
+
+            if h.page_count() == 2 {
+                // Le a pagina antiga e grava ela e a nova
+                assert_eq!(h.page_io(), (reads + 1, writes + 2));
+            }
+        }
+
+        assert_eq!(h.global_depth(), 1);
+    }
+
+    #[test]
+    fn test_buffer_pool() {
+        let path = temp_path("disk_pool.bin");
+        let mut h: DiskHash<i32, Record> = DiskHash::create(&path, 2, 4).unwrap();
+
+        for nseq in 0..200 {
+            h.insert(record(nseq)).unwrap();
+        }
+
+        // Com frames para todas as paginas so ha falta na primeira leitura
+        h.set_buffer_pool(h.page_count() as usize, Policy::Clock)
+            .unwrap();
+
+        let misses = h.buffer_pool().misses();
+
+        for _ in 0..2 {
+            for nseq in 0..200 {
+                h.search(&nseq).unwrap();
+            }
+        }
+
+        assert_eq!(
+            h.buffer_pool().misses() - misses,
+            h.directory()
+                .iter()
+                .collect::<std::collections::HashSet<_>>()
+                .len() as u64
+        );
+
+        // Com um frame quase todo acesso e uma falta, mas nada se perde
+        h.set_buffer_pool(1, Policy::LruK(2)).unwrap();
+
+        for nseq in 200..300 {
+            h.insert(record(nseq)).unwrap();
+        }
+
+        drop(h);
+
+        let mut h: DiskHash<i32, Record> = DiskHash::open(&path).unwrap();
+
+        assert_eq!(h.len(), 300);
+        assert_eq!(h.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_large_records_and_free_pages() {
+        let path = temp_path("disk_large.bin");
+        let mut h: DiskHash<i32, Record> = DiskHash::create(&path, 1, 2).unwrap();
+        let big = Record {
+            nseq: 7,
+            text: "Lorem ipsum ".repeat(300),
+        };
+
+        h.insert(big.clone()).unwrap();
+        h.insert(record(1)).unwrap();
+
+        let pages = h.page_count();
+
+        assert_eq!(h.search(&7).unwrap(), big);
+
+        // As paginas liberadas sao reaproveitadas
+        h.remove(&7).unwrap();
+        h.insert(big.clone()).unwrap();
+
+        assert_eq!(h.page_count(), pages);
+
+        drop(h);
+
+        let mut h: DiskHash<i32, Record> = DiskHash::open(&path).unwrap();
+
+        assert_eq!(h.search(&7).unwrap(), big);
+        assert_eq!(h.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let path = temp_path("disk_dups.bin");
+        let mut h: DiskHash<String, TextEntry> = DiskHash::create(&path, 1, 2).unwrap();
+
+        for rid in 0..20 {
+            h.insert(("igual".to_string(), rid)).unwrap();
+            h.insert((format!("texto {rid}"), rid)).unwrap();
+        }
+
+        assert_eq!(h.search_all(&"igual".to_string()).unwrap().len(), 20);
+        assert_eq!(h.check_invariants(), Ok(()));
+
+        // Mesma forma que a tabela em memoria
+        let mut m: ExtendibleHash<String, TextEntry> = ExtendibleHash::new(1, 2).unwrap();
+
+        for rid in 0..20 {
+            m.insert(("igual".to_string(), rid)).unwrap();
+            m.insert((format!("texto {rid}"), rid)).unwrap();
+        }
+
+        assert_eq!(h.global_depth(), m.global_depth());
+
+        for _ in 0..20 {
+            h.remove(&"igual".to_string()).unwrap();
+        }
+
+        assert!(h.search_all(&"igual".to_string()).unwrap().is_empty());
+        assert_eq!(h.check_invariants(), Ok(()));
+        assert_eq!(h.values().unwrap().len(), 20);
+    }
+
+    #[test]
+    fn test_checksums() {
+        let path = temp_path("disk_crc.bin");
+
+        {
+            let mut h: DiskHash<i32, Record> = DiskHash::create(&path, 1, 4).unwrap();
+
+            for nseq in 0..4 {
+                h.insert(record(nseq)).unwrap();
+            }
+        }
+
+        assert!(matches!(
+            DiskHash::<String, TextEntry>::open(&path),
+            Err(Error::WrongFormat { field: "tipo", .. })
+        ));
+
+        // Troca um byte no meio da ultima pagina
+        let mut bytes = std::fs::read(&path).unwrap();
+        let i = bytes.len() - 40;
+        bytes[i] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut h: DiskHash<i32, Record> = DiskHash::open(&path).unwrap();
+        let errors: Vec<Error> = (0..4).filter_map(|nseq| h.search(&nseq).err()).collect();
+
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(&errors[0], Error::Checksum { part, .. } if part == "pagina 1"));
+
+        drop(h);
+
+        // Proximo rid, no cabecalho
+        bytes[10] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+
+        assert!(matches!(
+            DiskHash::<i32, Record>::open(&path),
+            Err(Error::Checksum { offset: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_upgrade_legacy() {
+        let path = temp_path("disk_legacy.bin");
+        let records: Vec<Record> = (0..5).map(record).collect();
+
+        // Arquivo sem cabecalho das primeiras versoes, com os pares no
+        // balde A e os impares no B
+        let bytes = extendible_hash::legacy_image(
+            1,
+            4,
+            &[
+                (1, records.iter().step_by(2).cloned().collect()),
+                (1, records.iter().skip(1).step_by(2).cloned().collect()),
+            ],
+            &[0, 1],
+        );
+        std::fs::write(&path, &bytes).unwrap();
+
+        {
+            let mut h: DiskHash<i32, Record> = DiskHash::open(&path).unwrap();
+
+            assert_eq!(h.len(), 5);
+            assert_eq!(h.check_invariants(), Ok(()));
+        }
+
+        assert_eq!(std::fs::read(&path).unwrap()[..4], format::DISK_MAGIC);
+        assert!(!Path::new(&format!("{path}.tmp")).exists());
+
+        let mut h: DiskHash<i32, Record> = DiskHash::open(&path).unwrap();
+
+        for nseq in 0..5 {
+            assert_eq!(h.search(&nseq).unwrap(), record(nseq));
+        }
+    }
+}
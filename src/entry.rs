@@ -0,0 +1,242 @@
+use std::{
+    fmt::Debug,
+    hash::Hash,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{
+    error::{Error, Result},
+    extendible_hash::{ExtendibleHash, Rid},
+    traits::{Encode, Keyed},
+};
+
+/// A key in the table, found or not. Returned by `ExtendibleHash::entry`.
+pub enum Entry<'a, K, V: Encode> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+pub struct OccupiedEntry<'a, K, V: Encode> {
+    pub(crate) table: &'a mut ExtendibleHash<K, V>,
+    pub(crate) rid: Rid,
+}
+
+pub struct VacantEntry<'a, K, V: Encode> {
+    pub(crate) table: &'a mut ExtendibleHash<K, V>,
+    pub(crate) key: K,
+}
+
+/// A record borrowed from its slot. If its key was changed when it is
+/// dropped, it is moved to the bucket of the new key with the same rid; if
+/// it can't be, because the key is taken in a unique table, the old record
+/// is put back.
+pub struct RecordMut<'a, K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode + Clone,
+{
+    table: &'a mut ExtendibleHash<K, V>,
+    rid: Rid,
+    old: Option<V>,
+}
+
+impl<'a, K, V> Entry<'a, K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode,
+{
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+
+    /// Inserts `default` if the key is not in the table.
+    pub fn or_insert(self, default: V) -> Result<RecordMut<'a, K, V>>
+    where
+        V: Clone,
+    {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> Result<RecordMut<'a, K, V>>
+    where
+        V: Clone,
+    {
+        match self {
+            Entry::Occupied(e) => Ok(e.into_mut()),
+            Entry::Vacant(e) => e.insert(f()),
+        }
+    }
+
+    /// Runs `f` on the record if it exists. If `f` changed its key the
+    /// record is moved to the bucket of the new key, or left as it was and
+    /// `DuplicateKey` returned if a unique table already has that key.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Result<Self>
+    where
+        V: Clone,
+    {
+        match self {
+            Entry::Occupied(e) => {
+                let old = e.get().clone();
+                let (b, s) = e.loc();
+
+                f(&mut e.table.buckets[b].data[s]);
+                e.table.changes.buckets.insert(b);
+
+                if e.key() != old.key() {
+                    e.table.rekey(e.rid, old)?;
+                }
+
+                Ok(Entry::Occupied(e))
+            }
+            Entry::Vacant(e) => Ok(Entry::Vacant(e)),
+        }
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode,
+{
+    pub fn key(&self) -> &K {
+        self.get().key()
+    }
+
+    pub fn rid(&self) -> Rid {
+        self.rid
+    }
+
+    /// `(bucket, slot)` of the record.
+    fn loc(&self) -> (usize, usize) {
+        self.table.locations[&self.rid]
+    }
+
+    pub fn get(&self) -> &V {
+        let (b, s) = self.loc();
+
+        &self.table.buckets[b].data[s]
+    }
+
+    /// The record, moved to another bucket if its key is changed.
+    pub fn get_mut(&mut self) -> RecordMut<'_, K, V>
+    where
+        V: Clone,
+    {
+        RecordMut::new(self.table, self.rid)
+    }
+
+    pub fn into_mut(self) -> RecordMut<'a, K, V>
+    where
+        V: Clone,
+    {
+        RecordMut::new(self.table, self.rid)
+    }
+
+    /// Replaces the record in its slot, returning the old one. Fails with
+    /// `KeyChanged` if `value` has another key.
+    pub fn insert(&mut self, value: V) -> Result<V> {
+        if value.key() != self.key() {
+            return Err(Error::KeyChanged(format!("{:?}", self.key())));
+        }
+
+        value.validate(&self.table.layout)?;
+
+        let (b, s) = self.loc();
+        self.table.changes.buckets.insert(b);
+
+        Ok(std::mem::replace(&mut self.table.buckets[b].data[s], value))
+    }
+
+    /// Removes this record, also in a table with duplicate keys.
+    pub fn remove(self) -> Result<V> {
+        self.table.remove_rid(self.rid)
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Fails with `KeyChanged` if the key of `value` is not the key of the
+    /// entry.
+    pub fn insert(self, value: V) -> Result<RecordMut<'a, K, V>>
+    where
+        V: Clone,
+    {
+        if *value.key() != self.key {
+            return Err(Error::KeyChanged(format!("{:?}", self.key)));
+        }
+
+        let rid = self.table.insert(value)?;
+
+        Ok(RecordMut::new(self.table, rid))
+    }
+}
+
+impl<'a, K, V> RecordMut<'a, K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode + Clone,
+{
+    fn new(table: &'a mut ExtendibleHash<K, V>, rid: Rid) -> Self {
+        let (b, s) = table.locations[&rid];
+        let old = table.buckets[b].data[s].clone();
+
+        RecordMut {
+            table,
+            rid,
+            old: Some(old),
+        }
+    }
+}
+
+impl<'a, K, V> Deref for RecordMut<'a, K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode + Clone,
+{
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        let (b, s) = self.table.locations[&self.rid];
+
+        &self.table.buckets[b].data[s]
+    }
+}
+
+impl<'a, K, V> DerefMut for RecordMut<'a, K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode + Clone,
+{
+    fn deref_mut(&mut self) -> &mut V {
+        let (b, s) = self.table.locations[&self.rid];
+        self.table.changes.buckets.insert(b);
+
+        &mut self.table.buckets[b].data[s]
+    }
+}
+
+impl<'a, K, V> Drop for RecordMut<'a, K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode + Clone,
+{
+    fn drop(&mut self) {
+        if let Some(old) = self.old.take() {
+            if self.deref().key() != old.key() {
+                // Com a chave ocupada o registro antigo volta, nao ha como
+                // devolver o erro
+                let _ = self.table.rekey(self.rid, old);
+            }
+        }
+    }
+}
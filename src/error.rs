@@ -0,0 +1,87 @@
+use std::{fmt, io};
+
+#[derive(Debug)]
+pub enum Error {
+    DuplicateKey(String),
+    RecordTooLarge {
+        size: usize,
+        max: usize,
+    },
+    KeyNotFound(String),
+    /// An update changed the key of the record.
+    KeyChanged(String),
+    IndexNotFound(String),
+    /// A schema that can't be used, or a row that doesn't match its schema.
+    Schema(String),
+    /// The file could not be decoded at byte `offset`.
+    Corrupt {
+        offset: u64,
+        reason: String,
+    },
+    /// A file of another kind, format version or hash function.
+    WrongFormat {
+        field: &'static str,
+        expected: String,
+        found: String,
+    },
+    /// The part of the file starting at byte `offset` was damaged.
+    Checksum {
+        offset: u64,
+        part: String,
+    },
+    Io(io::Error),
+    DepthLimit(u8),
+    /// A merge threshold that is not a number from 0.0 to 1.0.
+    MergeThreshold(f32),
+    TooManyBuckets(usize),
+    /// Every frame of the buffer pool is pinned.
+    BufferFull(usize),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DuplicateKey(k) => write!(f, "Chave {k} ja existe"),
+            Error::RecordTooLarge { size, max } => {
+                write!(f, "Registro com {size} bytes, o maximo e {max}")
+            }
+            Error::KeyNotFound(k) => write!(f, "Chave {k} nao encontrada"),
+            Error::KeyChanged(k) => write!(f, "A chave {k} nao pode ser alterada"),
+            Error::IndexNotFound(i) => write!(f, "Indice {i} nao existe"),
+            Error::Schema(s) => write!(f, "Esquema invalido: {s}"),
+            Error::Corrupt { offset, reason } => {
+                write!(f, "Arquivo corrompido no byte {offset}: {reason}")
+            }
+            Error::WrongFormat {
+                field,
+                expected,
+                found,
+            } => write!(f, "Arquivo com {field} {found}, esperado {expected}"),
+            Error::Checksum { offset, part } => {
+                write!(f, "CRC de {part} nao confere (byte {offset})")
+            }
+            Error::Io(e) => write!(f, "Erro de E/S: {e}"),
+            Error::DepthLimit(d) => write!(f, "Global depth {d} acima do limite"),
+            Error::MergeThreshold(t) => write!(f, "Limiar {t} fora de 0 a 1"),
+            Error::TooManyBuckets(n) => write!(f, "{n} baldes nao cabem no arquivo"),
+            Error::BufferFull(n) => write!(f, "Todos os {n} frames estao fixados"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
@@ -0,0 +1,2591 @@
+use crate::{
+    bucket::{self, Bucket, SpillPages, PAGE_HEADER, SLOT_SIZE},
+    crc32::{crc32, Crc32},
+    entry::{Entry, OccupiedEntry, VacantEntry},
+    error::{Error, Result},
+    format,
+    hash_function::{self, HashFunction, LowBits},
+    iter::{IntoIter, Iter, IterMut},
+    reader::Reader,
+    traits::{Encode, Keyed},
+    wal::LogRecord,
+};
+use core::fmt;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    io::{Read, Write},
+    marker::PhantomData,
+};
+
+/// Buckets are numbered with `u32`, so the directory can have at most 2^32
+/// entries.
+pub const MAX_GLOBAL_DEPTH: u8 = 32;
+
+/// Max global depth of a new table, 2^15 directory entries. Keys whose
+/// hashes collide split buckets up to it, so it is kept small; raise it with
+/// `set_max_global_depth` for tables with more buckets.
+pub const DEFAULT_MAX_GLOBAL_DEPTH: u8 = 15;
+
+/// Bucket size used by `Default` and `FromIterator`.
+pub const DEFAULT_BUCKET_SIZE: u32 = 4;
+
+/// Record id. Given by `insert` and kept for the life of the record, while
+/// its `(bucket, slot)` changes on splits, merges and removes.
+pub type Rid = usize;
+
+/// Called with a rid and its new `(bucket, slot)`.
+pub type RelocationHook = Box<dyn FnMut(Rid, (usize, usize))>;
+
+/// Buckets and directory entries changed since the last `take_changes`,
+/// so a `Store` only writes those. Indexes past the end of the table are
+/// of buckets or entries that no longer exist.
+#[derive(Default)]
+pub(crate) struct Changes {
+    pub(crate) buckets: BTreeSet<usize>,
+    pub(crate) directory: BTreeSet<usize>,
+}
+
+pub struct ExtendibleHash<K, V: Encode> {
+    global_depth: u8,
+    directory: Vec<usize>,
+    pub(crate) buckets: Vec<Bucket<V>>,
+    free: Vec<usize>,
+    pub(crate) locations: HashMap<Rid, (usize, usize)>,
+    pub(crate) next_rid: Rid,
+    relocation_hook: Option<RelocationHook>,
+    merge_threshold: f32,
+    max_global_depth: u8,
+    hash_function: Box<dyn HashFunction>,
+    /// Record format of the buckets, e.g. the `Schema` of a `Row` table.
+    pub(crate) layout: V::Layout,
+    /// Bytes of every bucket page.
+    page_size: usize,
+    pub(crate) changes: Changes,
+    /// Splits and directory doublings for the log of a `Table`, kept only
+    /// after `log_structure`.
+    structure: Option<Vec<LogRecord>>,
+    key: PhantomData<K>,
+}
+
+impl<K, V> ExtendibleHash<K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode,
+{
+    /// Fails with `DepthLimit` if `global_depth` is above
+    /// `DEFAULT_MAX_GLOBAL_DEPTH`; deeper directories are reached by
+    /// splitting after `set_max_global_depth`.
+    pub fn new(global_depth: u8, bucket_size: u32) -> Result<Self> {
+        Self::with_hash_function(global_depth, bucket_size, Box::new(LowBits))
+    }
+
+    pub fn with_hash_function(
+        global_depth: u8,
+        bucket_size: u32,
+        hash_function: Box<dyn HashFunction>,
+    ) -> Result<Self> {
+        Self::with_layout(
+            global_depth,
+            bucket_size,
+            hash_function,
+            V::Layout::default(),
+        )
+    }
+
+    /// A table whose buckets store values in the given layout, e.g.
+    /// `HashRows::with_layout(1, 4, Box::new(LowBits), schema)`. Pages hold
+    /// `bucket_size` records of nominal size, more if they are shorter.
+    pub fn with_layout(
+        global_depth: u8,
+        bucket_size: u32,
+        hash_function: Box<dyn HashFunction>,
+        layout: V::Layout,
+    ) -> Result<Self> {
+        let page_size = bucket::page_size_for(bucket_size, V::nominal_size(&layout));
+
+        Self::with_page_size(global_depth, page_size, hash_function, layout)
+    }
+
+    /// A table with bucket pages of `page_size` bytes, kept between
+    /// `MIN_PAGE_SIZE` and `MAX_PAGE_SIZE`.
+    pub fn with_page_size(
+        global_depth: u8,
+        page_size: usize,
+        hash_function: Box<dyn HashFunction>,
+        layout: V::Layout,
+    ) -> Result<Self> {
+        // Um diretorio de 2^32 entradas nem chega a ser alocado
+        if global_depth > DEFAULT_MAX_GLOBAL_DEPTH {
+            return Err(Error::DepthLimit(global_depth));
+        }
+
+        Ok(Self::build(global_depth, page_size, hash_function, layout))
+    }
+
+    /// `with_page_size` for a `global_depth` already checked.
+    fn build(
+        global_depth: u8,
+        page_size: usize,
+        hash_function: Box<dyn HashFunction>,
+        layout: V::Layout,
+    ) -> Self {
+        let page_size = page_size.clamp(bucket::MIN_PAGE_SIZE, bucket::MAX_PAGE_SIZE);
+        let bucket_size = ((page_size - PAGE_HEADER) / (SLOT_SIZE + V::nominal_size(&layout)))
+            .clamp(1, u32::MAX as usize) as u32;
+        let size = 1 << global_depth;
+
+        let mut directory = Vec::with_capacity(size);
+        let mut buckets = Vec::with_capacity(size);
+
+        for i in 0..size {
+            buckets.push(Bucket::new(i as u32, global_depth, bucket_size, page_size));
+
+            directory.push(i);
+        }
+
+        ExtendibleHash {
+            global_depth,
+            directory,
+            buckets,
+            free: Vec::new(),
+            locations: HashMap::new(),
+            next_rid: 0,
+            relocation_hook: None,
+            merge_threshold: 1.0,
+            max_global_depth: DEFAULT_MAX_GLOBAL_DEPTH,
+            hash_function,
+            layout,
+            page_size,
+            changes: Changes::default(),
+            structure: None,
+            key: PhantomData,
+        }
+    }
+
+    /// Buddy buckets are merged on remove when their records take at most
+    /// `threshold` of a page. Defaults to 1.0 (whenever they fit in one
+    /// page); 0.0 only merges two empty buckets. Fails with `MergeThreshold`
+    /// outside `0.0..=1.0`, where merged buckets could overflow their page.
+    pub fn set_merge_threshold(&mut self, threshold: f32) -> Result<()> {
+        self.merge_threshold = check_merge_threshold(threshold)?;
+
+        Ok(())
+    }
+
+    /// Once a full bucket has `local_depth == max_global_depth` it is no longer
+    /// split; new records go to a chain of overflow buckets instead.
+    pub fn set_max_global_depth(&mut self, max_global_depth: u8) -> Result<()> {
+        if max_global_depth > MAX_GLOBAL_DEPTH {
+            return Err(Error::DepthLimit(max_global_depth));
+        }
+
+        self.max_global_depth = max_global_depth.max(self.global_depth);
+
+        Ok(())
+    }
+
+    /// `hook(rid, (bucket, slot))` is called whenever a record is stored in
+    /// a slot: on insert and every time a split, merge or remove moves it.
+    pub fn set_relocation_hook<F: FnMut(Rid, (usize, usize)) + 'static>(&mut self, hook: F) {
+        self.relocation_hook = Some(Box::new(hook));
+    }
+
+    fn hash(&self, key: &K) -> u64 {
+        let mut hasher = self.hash_function.hasher();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Directory index of `key`: the low `global_depth` bits of its hash.
+    pub fn hash_fun(&self, key: &K) -> usize {
+        (self.hash(key) & ((1 << self.global_depth) - 1)) as usize
+    }
+
+    pub fn insert(&mut self, value: V) -> Result<Rid> {
+        self.check_new(&value)?;
+
+        let rid = self.next_rid;
+        self.next_rid += 1;
+
+        self.place(rid, value);
+
+        Ok(rid)
+    }
+
+    /// Puts back a record taken out with `remove_rid`, under the same rid.
+    pub(crate) fn reinsert(&mut self, rid: Rid, value: V) -> Result<()> {
+        if rid >= self.next_rid || self.locations.contains_key(&rid) {
+            return Err(Error::DuplicateKey(format!("rid {rid}")));
+        }
+
+        self.check_new(&value)?;
+        self.place(rid, value);
+
+        Ok(())
+    }
+
+    /// Fails if `value` can't go in the table as a new record.
+    fn check_new(&self, value: &V) -> Result<()> {
+        value.validate(&self.layout)?;
+
+        if V::UNIQUE && self.locate(value.key()).is_ok() {
+            return Err(Error::DuplicateKey(format!("{:?}", value.key())));
+        }
+
+        Ok(())
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        match self.locate(&key) {
+            Ok((b, s)) => {
+                let rid = self.buckets[b].ids[s];
+
+                Entry::Occupied(OccupiedEntry { table: self, rid })
+            }
+            Err(_) => Entry::Vacant(VacantEntry { table: self, key }),
+        }
+    }
+
+    /// Overwrites the record with the same key in its slot, returning the old one.
+    pub fn replace(&mut self, value: V) -> Result<V> {
+        value.validate(&self.layout)?;
+
+        let (b, s) = self.locate(value.key())?;
+        self.changes.buckets.insert(b);
+
+        Ok(std::mem::replace(&mut self.buckets[b].data[s], value))
+    }
+
+    /// Replaces the record if the key exists, inserts it otherwise.
+    pub fn upsert(&mut self, value: V) -> Result<Option<V>> {
+        match self.entry(value.key().clone()) {
+            Entry::Occupied(mut e) => e.insert(value).map(Some),
+            Entry::Vacant(e) => e.table.insert(value).map(|_| None),
+        }
+    }
+
+    /// Runs `f` on a copy of the record and writes it back in the same slot,
+    /// returning the old record. Fails with `KeyChanged` if `f` changed the key.
+    pub fn update<F: FnOnce(&mut V)>(&mut self, key: &K, f: F) -> Result<V>
+    where
+        V: Clone,
+    {
+        let (b, s) = self.locate(key)?;
+
+        let mut value = self.buckets[b].data[s].clone();
+        f(&mut value);
+
+        if value.key() != key {
+            return Err(Error::KeyChanged(format!("{key:?}")));
+        }
+
+        value.validate(&self.layout)?;
+        self.changes.buckets.insert(b);
+
+        Ok(std::mem::replace(&mut self.buckets[b].data[s], value))
+    }
+
+    fn place(&mut self, rid: Rid, value: V) {
+        let h = self.hash_fun(value.key());
+        let bucket_index = self.directory[h];
+        let chain: Vec<usize> = self.chain(bucket_index).collect();
+
+        // Se todos tem o mesmo hash (chaves repetidas) dividir nunca separa
+        let hash = self.hash(value.key());
+        let same_hash = chain.iter().all(|b| {
+            self.buckets[*b]
+                .data
+                .iter()
+                .all(|v| self.hash(v.key()) == hash)
+        });
+
+        if let Some(b) = chain
+            .iter()
+            .find(|b| self.buckets[**b].fits(&value, &self.layout))
+        {
+            self.buckets[*b].insert(rid, value, &self.layout);
+            self.relocate(*b, self.buckets[*b].data.len() - 1);
+        } else if self.buckets[bucket_index].local_depth < self.max_global_depth && !same_hash {
+            self.split(h, rid, value);
+        } else {
+            // Nao da para dividir: encadeia um balde de overflow
+            let overflow = self.new_bucket(
+                self.buckets[bucket_index].local_depth,
+                self.buckets[bucket_index].size,
+            );
+
+            self.buckets[*chain.last().unwrap()].overflow = Some(overflow);
+            self.changes.buckets.insert(*chain.last().unwrap());
+            self.buckets[overflow].insert(rid, value, &self.layout);
+            self.relocate(overflow, 0);
+        }
+    }
+
+    /// Records where the slots from `from` on of a bucket now live.
+    fn relocate(&mut self, bucket_index: usize, from: usize) {
+        self.changes.buckets.insert(bucket_index);
+
+        for slot in from..self.buckets[bucket_index].data.len() {
+            let rid = self.buckets[bucket_index].ids[slot];
+
+            self.locations.insert(rid, (bucket_index, slot));
+
+            if let Some(hook) = &mut self.relocation_hook {
+                hook(rid, (bucket_index, slot));
+            }
+        }
+    }
+
+    fn split(&mut self, dir_index: usize, rid: Rid, value: V) {
+        self.split_bucket(self.directory[dir_index]);
+        self.place(rid, value);
+    }
+
+    fn split_bucket(&mut self, bucket_index: usize) {
+        // Dobra diretorio se ld = gd
+        if self.buckets[bucket_index].local_depth == self.global_depth {
+            self.double_directory();
+        }
+
+        // Retira dado do balde (e do overflow) e incrementa ld
+        let mut bkp = std::mem::take(&mut self.buckets[bucket_index].data);
+        let mut bkp_ids = std::mem::take(&mut self.buckets[bucket_index].ids);
+
+        let overflow: Vec<usize> = self.chain(bucket_index).skip(1).collect();
+        self.buckets[bucket_index].overflow = None;
+
+        for o in overflow {
+            bkp.append(&mut self.buckets[o].data);
+            bkp_ids.append(&mut self.buckets[o].ids);
+            self.free_bucket(o);
+        }
+
+        let bit = self.buckets[bucket_index].local_depth;
+        self.buckets[bucket_index].local_depth += 1;
+        self.changes.buckets.insert(bucket_index);
+        self.note(LogRecord::Split {
+            bucket: bucket_index as u32,
+            local_depth: bit + 1,
+        });
+
+        // Cria balde novo
+        let new_index = self.new_bucket(
+            self.buckets[bucket_index].local_depth,
+            self.buckets[bucket_index].size,
+        );
+
+        // Entradas que apontavam para o balde e tem o novo bit ligado passam
+        // a apontar para o balde novo
+        for (i, d) in self.directory.iter_mut().enumerate() {
+            if *d == bucket_index && (i >> bit) & 1 == 1 {
+                *d = new_index;
+                self.changes.directory.insert(i);
+            }
+        }
+
+        // Reorganizar entradas (os rids acompanham os registros)
+        for (r, v) in bkp_ids.into_iter().zip(bkp) {
+            self.place(r, v);
+        }
+    }
+
+    /// Reuses a freed bucket slot if there is one.
+    fn new_bucket(&mut self, local_depth: u8, size: u32) -> usize {
+        if let Some(i) = self.free.pop() {
+            self.buckets[i].local_depth = local_depth;
+            self.buckets[i].overflow = None;
+            self.changes.buckets.insert(i);
+            return i;
+        }
+
+        self.buckets.push(Bucket::new(
+            self.buckets.len() as u32,
+            local_depth,
+            size,
+            self.page_size,
+        ));
+        self.changes.buckets.insert(self.buckets.len() - 1);
+
+        self.buckets.len() - 1
+    }
+
+    fn free_bucket(&mut self, bucket_index: usize) {
+        self.changes.buckets.insert(bucket_index);
+        self.buckets[bucket_index].data.clear();
+        self.buckets[bucket_index].ids.clear();
+        self.buckets[bucket_index].overflow = None;
+        self.free.push(bucket_index);
+
+        // Baldes livres no fim do vetor sao descartados
+        while let Some(pos) = self.free.iter().position(|i| *i == self.buckets.len() - 1) {
+            self.free.swap_remove(pos);
+            self.buckets.pop();
+        }
+    }
+
+    fn double_directory(&mut self) {
+        let n = self.directory.len();
+
+        for i in 0..n {
+            self.directory.push(self.directory[i])
+        }
+
+        self.changes.directory.extend(n..2 * n);
+
+        self.global_depth += 1;
+        self.note(LogRecord::Doubling {
+            global_depth: self.global_depth,
+        });
+    }
+
+    fn halve_directory(&mut self) {
+        self.directory.truncate(self.directory.len() / 2);
+
+        self.global_depth -= 1;
+    }
+
+    /// In a table with duplicate keys, removes the first record found.
+    pub fn remove(&mut self, key: &K) -> Result<V> {
+        let (bucket_index, slot) = self.locate(key)?;
+
+        Ok(self.remove_at(bucket_index, slot))
+    }
+
+    /// Removes the record with a rid returned by `insert` or `search`.
+    pub fn remove_rid(&mut self, rid: Rid) -> Result<V> {
+        let (bucket_index, slot) = self
+            .location(rid)
+            .ok_or_else(|| Error::KeyNotFound(format!("rid {rid}")))?;
+
+        Ok(self.remove_at(bucket_index, slot))
+    }
+
+    /// Moves the record `rid`, whose key was changed in its slot, to the
+    /// bucket of the new key. If the record is invalid or a unique table
+    /// already has that key, `old` is put back instead.
+    pub(crate) fn rekey(&mut self, rid: Rid, old: V) -> Result<()> {
+        let (bucket_index, slot) = self.locations[&rid];
+        let value = self.remove_at(bucket_index, slot);
+
+        let check = self.check_new(&value);
+
+        match check {
+            Ok(()) => self.place(rid, value),
+            Err(_) => self.place(rid, old),
+        }
+
+        check
+    }
+
+    fn remove_at(&mut self, bucket_index: usize, slot: usize) -> V {
+        let h = self.hash_fun(self.buckets[bucket_index].data[slot].key());
+
+        let value = self.buckets[bucket_index].data.remove(slot);
+        let rid = self.buckets[bucket_index].ids.remove(slot);
+
+        // Os registros seguintes andam uma posicao
+        self.locations.remove(&rid);
+        self.relocate(bucket_index, slot);
+
+        // Overflow vazio sai da cadeia
+        if bucket_index != self.directory[h] && self.buckets[bucket_index].data.is_empty() {
+            let prev = self
+                .chain(self.directory[h])
+                .find(|b| self.buckets[*b].overflow == Some(bucket_index))
+                .unwrap();
+
+            self.buckets[prev].overflow = self.buckets[bucket_index].overflow;
+            self.changes.buckets.insert(prev);
+            self.free_bucket(bucket_index);
+        }
+
+        self.merge(h);
+
+        value
+    }
+
+    /// Merges the bucket at `dir_index` with its buddy while they fit under
+    /// the merge threshold, halving the directory whenever possible.
+    fn merge(&mut self, mut dir_index: usize) {
+        loop {
+            let bucket_index = self.directory[dir_index];
+            let local_depth = self.buckets[bucket_index].local_depth;
+
+            if local_depth == 0 {
+                break;
+            }
+
+            // O buddy difere apenas no bit ld - 1
+            let bit = 1 << (local_depth - 1);
+            let buddy_index = self.directory[dir_index ^ bit];
+
+            if buddy_index == bucket_index
+                || self.buckets[buddy_index].local_depth != local_depth
+                || self.buckets[bucket_index].overflow.is_some()
+                || self.buckets[buddy_index].overflow.is_some()
+            {
+                break;
+            }
+
+            let combined = self.buckets[bucket_index].used(&self.layout)
+                + self.buckets[buddy_index].used(&self.layout);
+            let limit = (self.page_size - PAGE_HEADER) as f32 * self.merge_threshold;
+
+            if combined as f32 > limit {
+                break;
+            }
+
+            // Mantem o balde com bit 0 e libera o outro
+            let (keep, gone) = if dir_index & bit == 0 {
+                (bucket_index, buddy_index)
+            } else {
+                (buddy_index, bucket_index)
+            };
+
+            let data = std::mem::take(&mut self.buckets[gone].data);
+            let ids = std::mem::take(&mut self.buckets[gone].ids);
+            let n = self.buckets[keep].data.len();
+
+            self.buckets[keep].data.extend(data);
+            self.buckets[keep].ids.extend(ids);
+            self.buckets[keep].local_depth -= 1;
+            self.relocate(keep, n);
+
+            for (i, d) in self.directory.iter_mut().enumerate() {
+                if *d == gone {
+                    *d = keep;
+                    self.changes.directory.insert(i);
+                }
+            }
+
+            self.free_bucket(gone);
+
+            // Reduz diretorio se nenhum balde tem ld = gd
+            while self.global_depth > 0
+                && self
+                    .directory
+                    .iter()
+                    .all(|d| self.buckets[*d].local_depth < self.global_depth)
+            {
+                self.halve_directory();
+            }
+
+            dir_index &= self.directory.len() - 1;
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|v| v.key())
+    }
+
+    /// Removes every record, merging the buckets back as `remove` does.
+    pub fn drain(&mut self) -> std::vec::IntoIter<V> {
+        self.retain_into(|_| false).into_iter()
+    }
+
+    /// Keeps only the records for which `f` returns true.
+    pub fn retain<F: FnMut(&V) -> bool>(&mut self, f: F) {
+        self.retain_into(f);
+    }
+
+    /// Returns the removed records.
+    fn retain_into<F: FnMut(&V) -> bool>(&mut self, mut f: F) -> Vec<V> {
+        // Por rid: com chaves repetidas, remover pela chave pode levar um
+        // registro que fica
+        let rids: Vec<Rid> = self
+            .buckets
+            .iter()
+            .flat_map(|b| b.data.iter().zip(&b.ids))
+            .filter(|(v, _)| !f(v))
+            .map(|(_, rid)| *rid)
+            .collect();
+
+        rids.into_iter()
+            .filter_map(|rid| self.remove_rid(rid).ok())
+            .collect()
+    }
+
+    pub fn search(&self, key: &K) -> Result<Rid> {
+        let (b, s) = self.locate(key)?;
+
+        Ok(self.buckets[b].ids[s])
+    }
+
+    /// Rids of every record with `key`. Only tables of non-unique values can
+    /// have more than one.
+    pub fn search_all(&self, key: &K) -> Vec<Rid> {
+        let h = self.hash_fun(key);
+
+        self.chain(self.directory[h])
+            .flat_map(|b| {
+                let bucket = &self.buckets[b];
+
+                (0..bucket.data.len())
+                    .filter(move |s| bucket.data[*s].key() == key)
+                    .map(move |s| bucket.ids[s])
+            })
+            .collect()
+    }
+
+    /// `(bucket, slot)` of the record with `key`.
+    fn locate(&self, key: &K) -> Result<(usize, usize)> {
+        let h = self.hash_fun(key);
+
+        self.chain(self.directory[h])
+            .find_map(|b| Some((b, self.buckets[b].search(key)?)))
+            .ok_or_else(|| Error::KeyNotFound(format!("{key:?}")))
+    }
+
+    /// Checks the directory and bucket structure, returning a description of
+    /// the first problem found.
+    pub fn check_invariants(&self) -> std::result::Result<(), String> {
+        if self.directory.len() != 1 << self.global_depth {
+            return Err(format!(
+                "Directory has {} entries, expected 2^{}",
+                self.directory.len(),
+                self.global_depth
+            ));
+        }
+
+        let overflow: Vec<usize> = self.buckets.iter().filter_map(|b| b.overflow).collect();
+
+        for (b, bucket) in self.buckets.iter().enumerate() {
+            let refs: Vec<usize> = (0..self.directory.len())
+                .filter(|i| self.directory[*i] == b)
+                .collect();
+
+            if self.free.contains(&b) {
+                if !refs.is_empty() || !bucket.data.is_empty() || overflow.contains(&b) {
+                    return Err(format!("Free bucket {} is still in use", bucket.label()));
+                }
+                continue;
+            }
+
+            if overflow.contains(&b) {
+                if !refs.is_empty() || overflow.iter().filter(|o| **o == b).count() > 1 {
+                    return Err(format!(
+                        "Overflow bucket {} must belong to exactly one chain",
+                        bucket.label()
+                    ));
+                }
+                continue;
+            }
+
+            if bucket.local_depth > self.global_depth {
+                return Err(format!(
+                    "Bucket {} has local depth {} > global depth {}",
+                    bucket.label(),
+                    bucket.local_depth,
+                    self.global_depth
+                ));
+            }
+
+            let expected = 1 << (self.global_depth - bucket.local_depth);
+
+            if refs.len() != expected {
+                return Err(format!(
+                    "Bucket {} is referenced by {} entries, expected {}",
+                    bucket.label(),
+                    refs.len(),
+                    expected
+                ));
+            }
+
+            let mask = (1 << bucket.local_depth) - 1;
+
+            if refs.iter().any(|i| i & mask != refs[0] & mask) {
+                return Err(format!(
+                    "Entries pointing to bucket {} differ in the low {} bits",
+                    bucket.label(),
+                    bucket.local_depth
+                ));
+            }
+
+            for c in self.chain(b) {
+                let o = &self.buckets[c];
+
+                // Registros que cresceram podem passar da pagina, mas tem que
+                // caber depois de ir para paginas de overflow
+                if o.serialize(&self.layout, &mut Vec::<Vec<u8>>::new())
+                    .is_err()
+                {
+                    return Err(format!("Bucket {} does not fit in its page", o.label()));
+                }
+
+                if o.local_depth != bucket.local_depth {
+                    return Err(format!(
+                        "Overflow bucket {} has a different local depth than {}",
+                        o.label(),
+                        bucket.label()
+                    ));
+                }
+
+                if o.ids.len() != o.data.len() {
+                    return Err(format!("Bucket {} has a rid count mismatch", o.label()));
+                }
+
+                for (slot, rid) in o.ids.iter().enumerate() {
+                    if self.locations.get(rid) != Some(&(c, slot)) {
+                        return Err(format!(
+                            "Rid {rid} is not mapped to bucket {} slot {slot}",
+                            o.label()
+                        ));
+                    }
+                }
+
+                for v in &o.data {
+                    if self.directory[self.hash_fun(v.key())] != b {
+                        return Err(format!(
+                            "Key {} is in bucket {} but hashes elsewhere",
+                            v.label(),
+                            o.label()
+                        ));
+                    }
+                }
+            }
+        }
+
+        if self.locations.len() != self.len() {
+            return Err(format!(
+                "{} rids for {} records",
+                self.locations.len(),
+                self.len()
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl<K, V: Encode> ExtendibleHash<K, V> {
+    pub fn global_depth(&self) -> u8 {
+        self.global_depth
+    }
+
+    pub fn max_global_depth(&self) -> u8 {
+        self.max_global_depth
+    }
+
+    pub fn hash_function(&self) -> &dyn HashFunction {
+        self.hash_function.as_ref()
+    }
+
+    pub fn layout(&self) -> &V::Layout {
+        &self.layout
+    }
+
+    /// Bytes of a bucket page.
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Bucket index for each of the `2^global_depth` directory entries.
+    pub fn directory(&self) -> &[usize] {
+        &self.directory
+    }
+
+    /// Number of bucket slots, including free and overflow buckets.
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn bucket(&self, index: usize) -> Option<&Bucket<V>> {
+        self.buckets.get(index)
+    }
+
+    /// The record with a rid returned by `insert` or `search`.
+    pub fn get(&self, rid: Rid) -> Option<&V> {
+        let (b, s) = *self.locations.get(&rid)?;
+
+        Some(&self.buckets[b].data[s])
+    }
+
+    /// Current `(bucket, slot)` of a record.
+    pub fn location(&self, rid: Rid) -> Option<(usize, usize)> {
+        self.locations.get(&rid).copied()
+    }
+
+    /// Number of records.
+    pub fn len(&self) -> usize {
+        self.buckets.iter().map(|b| b.data.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Visits each bucket once, in bucket order.
+    pub fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            buckets: self.buckets.iter(),
+            data: [].iter(),
+        }
+    }
+
+    /// Changing the key of a record through this iterator leaves it in the
+    /// wrong bucket.
+    pub fn iter_mut(&mut self) -> IterMut<'_, V> {
+        // Qualquer registro pode mudar
+        self.changes.buckets.extend(0..self.buckets.len());
+
+        IterMut {
+            buckets: self.buckets.iter_mut(),
+            data: [].iter_mut(),
+        }
+    }
+
+    /// Same as `iter`: the stored values are the records themselves.
+    pub fn values(&self) -> Iter<'_, V> {
+        self.iter()
+    }
+
+    /// The bucket followed by its overflow buckets.
+    fn chain(&self, bucket_index: usize) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(Some(bucket_index), |b| self.buckets[*b].overflow)
+    }
+}
+
+impl<K, V: Encode> IntoIterator for ExtendibleHash<K, V> {
+    type Item = V;
+    type IntoIter = IntoIter<V>;
+
+    fn into_iter(self) -> IntoIter<V> {
+        IntoIter {
+            buckets: self.buckets.into_iter(),
+            data: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<'a, K, V: Encode> IntoIterator for &'a ExtendibleHash<K, V> {
+    type Item = &'a V;
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Iter<'a, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V: Encode> IntoIterator for &'a mut ExtendibleHash<K, V> {
+    type Item = &'a mut V;
+    type IntoIter = IterMut<'a, V>;
+
+    fn into_iter(self) -> IterMut<'a, V> {
+        self.iter_mut()
+    }
+}
+
+impl<K, V> Default for ExtendibleHash<K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode,
+{
+    fn default() -> Self {
+        let layout = V::Layout::default();
+        let page_size = bucket::page_size_for(DEFAULT_BUCKET_SIZE, V::nominal_size(&layout));
+
+        Self::build(0, page_size, Box::new(LowBits), layout)
+    }
+}
+
+/// Panics if `insert` rejects a record (duplicate key or too large).
+impl<K, V> Extend<V> for ExtendibleHash<K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode,
+{
+    fn extend<I: IntoIterator<Item = V>>(&mut self, iter: I) {
+        for v in iter {
+            if let Err(e) = self.insert(v) {
+                panic!("{e}");
+            }
+        }
+    }
+}
+
+impl<K, V> FromIterator<V> for ExtendibleHash<K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode,
+{
+    fn from_iter<I: IntoIterator<Item = V>>(iter: I) -> Self {
+        let mut h = Self::default();
+        h.extend(iter);
+        h
+    }
+}
+
+impl<K, V: Encode> ExtendibleHash<K, V> {
+    /// The bytes `write_to` would write.
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        let mut encoded = Vec::new();
+        self.write_to(&mut encoded)?;
+
+        Ok(encoded)
+    }
+
+    /// Writes the table to `w` one page at a time, without keeping the
+    /// pages in memory.
+    pub fn write_to<W: Write + ?Sized>(&self, w: &mut W) -> Result<()> {
+        // | 4B magic | 1B versao | 1B tipo | 1B gd | 1B max gd | 1B hash | 4B bucket size |
+        // | 8B next rid | layout | 4B page size | 4B crc |
+        // | 4B p | o1 | crc |...| op | crc | 4B m | b1 | crc |...| bm | crc |
+        // | 8B n | 4B d1 | 4B d2 |...| 4B dn | 4B crc |
+
+        if self.global_depth > MAX_GLOBAL_DEPTH {
+            return Err(Error::DepthLimit(self.global_depth));
+        }
+
+        // u32::MAX marca balde sem overflow
+        if self.buckets.len() >= u32::MAX as usize {
+            return Err(Error::TooManyBuckets(self.buckets.len()));
+        }
+
+        let mut header: Vec<u8> = Vec::new();
+
+        // Numero magico, versao e tipo dos registros
+        format::write_header(format::TABLE_MAGIC, V::KIND, &mut header);
+
+        // Global Depth
+        header.push(self.global_depth.to_be_bytes()[0]);
+
+        // Max global depth
+        header.push(self.max_global_depth.to_be_bytes()[0]);
+
+        // Hash function
+        header.push(self.hash_function.id());
+
+        // Bucket size
+        header.extend_from_slice(&self.buckets[0].size.to_be_bytes());
+
+        // Proximo rid (rids de registros removidos nao sao reaproveitados)
+        header.extend_from_slice(&(self.next_rid as u64).to_be_bytes());
+
+        // Layout dos registros (vazio se o tipo so tem um formato)
+        V::write_layout(&self.layout, &mut header);
+
+        // Page size
+        header.extend_from_slice(&(self.page_size as u32).to_be_bytes());
+
+        write_crc(w, &header)?;
+
+        // Paginas de overflow dos registros grandes. Os baldes sao gerados
+        // uma vez para contar as paginas e outra para escreve-las
+        let mut count = SpillCount(0);
+
+        for b in &self.buckets {
+            b.serialize(&self.layout, &mut count)?;
+        }
+
+        w.write_all(&count.0.to_be_bytes())?;
+
+        let mut spill = SpillWriter {
+            w: &mut *w,
+            next: 0,
+        };
+
+        for b in &self.buckets {
+            b.serialize(&self.layout, &mut spill)?;
+        }
+
+        // Buckets
+        let mut spill = SpillCount(0);
+        w.write_all(&(self.buckets.len() as u32).to_be_bytes())?;
+
+        for b in &self.buckets {
+            write_crc(w, &b.serialize(&self.layout, &mut spill)?)?;
+        }
+
+        // Directory size
+        let size = (self.directory.len() as u64).to_be_bytes();
+        let mut crc = Crc32::new();
+
+        crc.update(&size);
+        w.write_all(&size)?;
+
+        // Diretorio em partes, sem montar tudo na memoria
+        for chunk in self.directory.chunks(4096) {
+            let bytes: Vec<u8> = chunk
+                .iter()
+                .flat_map(|d| (*d as u32).to_be_bytes())
+                .collect();
+
+            crc.update(&bytes);
+            w.write_all(&bytes)?;
+        }
+
+        w.write_all(&crc.finish().to_be_bytes())?;
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+impl<K, V> ExtendibleHash<K, V>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode,
+{
+    /// Reads a table written by `write_to` with one of the built-in hash
+    /// functions, or a headerless file of `format::LEGACY_VERSION`.
+    pub fn read_from<R: Read + ?Sized>(r: &mut R) -> Result<Self> {
+        Self::read(&mut Reader::new(r), None)
+    }
+
+    /// Reads a table written with a `Custom` hash function, which must be
+    /// the same one used when it was written.
+    pub fn read_from_with_hash_function<R: Read + ?Sized>(
+        r: &mut R,
+        hash_function: Box<dyn HashFunction>,
+    ) -> Result<Self> {
+        Self::read(&mut Reader::new(r), Some(hash_function))
+    }
+
+    /// Reads a headerless file of `format::LEGACY_VERSION` whose first byte,
+    /// the global depth, was already read. Its records are inserted again in
+    /// the order of the file, getting new rids.
+    fn read_legacy<R: Read>(
+        r: &mut Reader<R>,
+        global_depth: u8,
+        hash_function: Option<Box<dyn HashFunction>>,
+    ) -> Result<Self> {
+        // O diretorio tinha no maximo 2^15 entradas contadas em 2B
+        if global_depth > 15 {
+            return Err(Error::Corrupt {
+                offset: 0,
+                reason: format!("global depth {global_depth} invalida"),
+            });
+        }
+
+        // #baldes (2B)
+        let m = r.read_u16()? as usize;
+        let mut values = Vec::new();
+        let mut bucket_size = 1;
+
+        // Baldes (#baldes * (5B + size * slot))
+        for i in 0..m {
+            let offset = r.offset();
+
+            // Nome (3B), local depth e size (1B cada)
+            r.read_exact(&mut [0; 3])?;
+            let local_depth = r.read_u8()?;
+            let size = r.read_u8()?;
+
+            if local_depth > global_depth {
+                return Err(Error::Corrupt {
+                    offset,
+                    reason: format!(
+                        "balde {} com local depth {local_depth}",
+                        bucket::label(i as u32)
+                    ),
+                });
+            }
+
+            bucket_size = bucket_size.max(size as u32);
+
+            for _ in 0..size {
+                let offset = r.offset();
+
+                if let Some(v) = V::decode_legacy(r)? {
+                    values.push((offset, v));
+                }
+            }
+        }
+
+        // #direc (2B)
+        let n = r.read_u16()?;
+
+        if n != 1 << global_depth {
+            return Err(r.corrupt(format!(
+                "diretorio com {n} entradas para global depth {global_depth}"
+            )));
+        }
+
+        // Diretorios (2B cada)
+        for _ in 0..n {
+            let d = r.read_u16()?;
+
+            if d as usize >= m {
+                return Err(r.corrupt(format!("diretorio aponta para o balde {d}")));
+            }
+        }
+
+        let hash_function = hash_function.unwrap_or_else(|| Box::new(LowBits));
+        let mut table = Self::with_layout(
+            global_depth,
+            bucket_size,
+            hash_function,
+            V::Layout::default(),
+        )?;
+
+        for (offset, v) in values {
+            table.insert(v).map_err(|e| Error::Corrupt {
+                offset,
+                reason: e.to_string(),
+            })?;
+        }
+
+        // O arquivo foi lido inteiro, nada muda ao salvar de novo
+        table.take_changes();
+
+        Ok(table)
+    }
+
+    fn read<R: Read>(
+        r: &mut Reader<R>,
+        hash_function: Option<Box<dyn HashFunction>>,
+    ) -> Result<Self> {
+        r.begin_checksum();
+
+        let first = r.read_u8()?;
+
+        if V::LEGACY_SIZE > 0 && format::is_legacy(first, format::TABLE_MAGIC) {
+            return Self::read_legacy(r, first, hash_function);
+        }
+
+        // Numero magico, versao e tipo (6B)
+        format::read_header_from(r, first, format::TABLE_MAGIC, V::KIND)?;
+
+        // Global depth (1B)
+        let global_depth = r.read_u8()?;
+
+        if global_depth > MAX_GLOBAL_DEPTH {
+            return Err(r.corrupt(format!("global depth {global_depth} invalida")));
+        }
+
+        // Max global depth (1B)
+        let max_global_depth = r.read_u8()?;
+
+        // Hash function (1B)
+        let id = r.read_u8()?;
+
+        let hash_function = match hash_function {
+            Some(h) if h.id() == id => h,
+            Some(h) => {
+                return Err(Error::WrongFormat {
+                    field: "funcao de hash",
+                    expected: h.id().to_string(),
+                    found: id.to_string(),
+                })
+            }
+            None => hash_function::from_id(id)
+                .ok_or_else(|| r.corrupt(format!("funcao de hash {id} desconhecida")))?,
+        };
+
+        // Bucket size (4B)
+        let bucket_size = r.read_u32()?;
+
+        // Proximo rid (8B)
+        let next_rid = bucket::read_rid(r)?;
+
+        // Layout
+        let layout = V::read_layout(r)?;
+
+        // Page size (4B)
+        let page_size = r.read_u32()? as usize;
+
+        if page_size < bucket::MIN_PAGE_SIZE {
+            return Err(r.corrupt(format!("pagina de {page_size} bytes")));
+        }
+
+        // CRC do cabecalho (4B)
+        r.verify_checksum(0, "cabecalho")?;
+
+        // Paginas de overflow (4B + p * (page size + 4B crc))
+        let p = r.read_u32()? as usize;
+        let mut spill: Vec<Vec<u8>> = Vec::new();
+
+        for i in 0..p {
+            spill.push(read_page(r, page_size, || {
+                format!("pagina de overflow {i}")
+            })?);
+        }
+
+        // #baldes (4B)
+        let m = r.read_u32()? as usize;
+
+        // Baldes (#baldes * (page size + 4B crc))
+        let mut buckets: Vec<Bucket<V>> = Vec::new();
+        let mut locations = HashMap::new();
+
+        for i in 0..m {
+            let offset = r.offset();
+            let page = read_page(r, page_size, || format!("balde {i}"))?;
+            let b = Bucket::deserialize(
+                i as u32,
+                &layout,
+                page_size,
+                &mut spill,
+                &mut Reader::at(&page[..], offset),
+            )?;
+
+            if b.size != bucket_size {
+                return Err(Error::Corrupt {
+                    offset,
+                    reason: format!(
+                        "balde {} de tamanho {}, o cabecalho diz {bucket_size}",
+                        b.label(),
+                        b.size
+                    ),
+                });
+            }
+
+            if b.overflow.is_some_and(|o| o >= m) {
+                return Err(Error::Corrupt {
+                    offset,
+                    reason: format!("overflow do balde {} fora do arquivo", b.label()),
+                });
+            }
+
+            for (slot, rid) in b.ids.iter().enumerate() {
+                if *rid >= next_rid || locations.insert(*rid, (buckets.len(), slot)).is_some() {
+                    return Err(Error::Corrupt {
+                        offset,
+                        reason: format!("rid {rid} invalido no balde {}", b.label()),
+                    });
+                }
+            }
+
+            buckets.push(b);
+        }
+
+        let start = r.offset();
+        r.begin_checksum();
+
+        // #direc (8B)
+        let n = r.read_u64()?;
+
+        if n != 1 << global_depth {
+            return Err(r.corrupt(format!(
+                "diretorio com {n} entradas para global depth {global_depth}"
+            )));
+        }
+
+        // Diretorios
+        let mut directory: Vec<usize> = Vec::new();
+
+        for _ in 0..n {
+            directory.push(r.read_u32()? as usize);
+        }
+
+        // CRC do diretorio (4B), conferido antes das entradas
+        r.verify_checksum(start, "diretorio")?;
+
+        if let Some(i) = directory.iter().position(|d| *d >= m) {
+            return Err(Error::Corrupt {
+                offset: start + 8 + 4 * i as u64,
+                reason: format!("diretorio aponta para o balde {}", directory[i]),
+            });
+        }
+
+        let free = free_buckets(&buckets, &directory);
+
+        Ok(ExtendibleHash {
+            global_depth,
+            directory,
+            buckets,
+            free,
+            next_rid,
+            locations,
+            relocation_hook: None,
+            merge_threshold: 1.0,
+            max_global_depth,
+            hash_function,
+            layout,
+            page_size,
+            changes: Changes::default(),
+            structure: None,
+            key: PhantomData,
+        })
+    }
+
+    /// Starts keeping the splits and directory doublings, for
+    /// `take_structure`.
+    pub(crate) fn log_structure(&mut self) {
+        self.structure = Some(Vec::new());
+    }
+
+    /// Splits and directory doublings since the last call, in order.
+    pub(crate) fn take_structure(&mut self) -> Vec<LogRecord> {
+        self.structure
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn note(&mut self, record: LogRecord) {
+        if let Some(structure) = &mut self.structure {
+            structure.push(record);
+        }
+    }
+
+    /// Makes a logged split or doubling again, unless the table already
+    /// went past it.
+    pub(crate) fn redo_structure(&mut self, record: &LogRecord) {
+        match *record {
+            LogRecord::Doubling { global_depth }
+                if global_depth == self.global_depth + 1
+                    && global_depth <= self.max_global_depth =>
+            {
+                self.double_directory()
+            }
+            // Sem duplicar: a duplicacao vem antes no log
+            LogRecord::Split {
+                bucket,
+                local_depth,
+            } if self.directory.contains(&(bucket as usize))
+                && self.buckets[bucket as usize].local_depth + 1 == local_depth
+                && local_depth <= self.global_depth =>
+            {
+                self.split_bucket(bucket as usize)
+            }
+            _ => {}
+        }
+    }
+
+    /// Changes since the last call, emptying them.
+    pub(crate) fn take_changes(&mut self) -> Changes {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// Replaces the shape and buckets of the table with ones read by a
+    /// `Store`, which checked that they fit together.
+    pub(crate) fn restore(
+        &mut self,
+        global_depth: u8,
+        max_global_depth: u8,
+        next_rid: Rid,
+        buckets: Vec<Bucket<V>>,
+        directory: Vec<usize>,
+    ) {
+        self.free = free_buckets(&buckets, &directory);
+        self.locations = buckets
+            .iter()
+            .enumerate()
+            .flat_map(|(b, bucket)| {
+                bucket
+                    .ids
+                    .iter()
+                    .enumerate()
+                    .map(move |(s, r)| (*r, (b, s)))
+            })
+            .collect();
+        self.global_depth = global_depth;
+        self.max_global_depth = max_global_depth;
+        self.next_rid = next_rid;
+        self.buckets = buckets;
+        self.directory = directory;
+        self.changes = Changes::default();
+    }
+}
+
+/// Buckets without a pointer in the directory and out of every overflow
+/// chain.
+fn free_buckets<V>(buckets: &[Bucket<V>], directory: &[usize]) -> Vec<usize> {
+    let mut used = vec![false; buckets.len()];
+
+    for i in directory
+        .iter()
+        .copied()
+        .chain(buckets.iter().filter_map(|b| b.overflow))
+    {
+        used[i] = true;
+    }
+
+    (0..buckets.len()).filter(|i| !used[*i]).collect()
+}
+
+/// The merge threshold if it is from 0.0 to 1.0.
+pub(crate) fn check_merge_threshold(threshold: f32) -> Result<f32> {
+    if (0.0..=1.0).contains(&threshold) {
+        Ok(threshold)
+    } else {
+        Err(Error::MergeThreshold(threshold))
+    }
+}
+
+/// Writes `bytes` followed by their CRC-32.
+fn write_crc<W: Write + ?Sized>(w: &mut W, bytes: &[u8]) -> Result<()> {
+    w.write_all(bytes)?;
+    w.write_all(&crc32(bytes).to_be_bytes())?;
+
+    Ok(())
+}
+
+/// Numbers overflow pages without keeping them, to count them and to
+/// serialize the buckets that point to them in `write_to`.
+struct SpillCount(u32);
+
+impl SpillPages for SpillCount {
+    fn write_page(&mut self, _page: Vec<u8>) -> Result<u32> {
+        self.0 += 1;
+        Ok(self.0 - 1)
+    }
+
+    fn read_page(&mut self, _id: u32) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+}
+
+/// Writes overflow pages to `w` as they are made, numbered like
+/// `SpillCount` does.
+struct SpillWriter<'a, W: Write + ?Sized> {
+    w: &'a mut W,
+    next: u32,
+}
+
+impl<W: Write + ?Sized> SpillPages for SpillWriter<'_, W> {
+    fn write_page(&mut self, page: Vec<u8>) -> Result<u32> {
+        write_crc(self.w, &page)?;
+        self.next += 1;
+        Ok(self.next - 1)
+    }
+
+    fn read_page(&mut self, _id: u32) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+}
+
+/// Reads a page followed by its CRC-32.
+fn read_page<R: Read>(
+    r: &mut Reader<R>,
+    page_size: usize,
+    part: impl FnOnce() -> String,
+) -> Result<Vec<u8>> {
+    let start = r.offset();
+    let mut page = vec![0; page_size];
+
+    r.begin_checksum();
+    r.read_exact(&mut page)?;
+    r.verify_checksum(start, part())?;
+
+    Ok(page)
+}
+
+/// A `HashAlt1` file as the first releases wrote it, from its buckets as
+/// `(local depth, records)`, all of `size` slots.
+#[cfg(test)]
+pub(crate) fn legacy_image(
+    global_depth: u8,
+    size: u8,
+    buckets: &[(u8, Vec<crate::record::Record>)],
+    directory: &[u16],
+) -> Vec<u8> {
+    // | 1B gd | 2B m | b1 | b2 |...| bm | 2B n | 2B d1 | 2B d2 |...| 2B dn |
+    let mut encoded = vec![global_depth];
+    encoded.extend_from_slice(&(buckets.len() as u16).to_be_bytes());
+
+    for (i, (local_depth, records)) in buckets.iter().enumerate() {
+        // | 3B name | 1B ld | 1B size | R1 100B | R2 100B |...| Rsize 100B |
+        let mut b = bucket::label(i as u32).into_bytes();
+        b.resize(3, 0);
+        b.push(*local_depth);
+        b.push(size);
+
+        let n = b.len();
+        b.resize(n + 100 * size as usize, 0);
+
+        for (i, r) in records.iter().enumerate() {
+            let start = n + 100 * i;
+
+            b[start..start + 4].copy_from_slice(&r.nseq.to_be_bytes());
+            b[start + 4..start + 4 + r.text.len()].copy_from_slice(r.text.as_bytes());
+        }
+
+        encoded.extend_from_slice(&b);
+    }
+
+    encoded.extend_from_slice(&(directory.len() as u16).to_be_bytes());
+
+    for d in directory {
+        encoded.extend_from_slice(&d.to_be_bytes());
+    }
+
+    encoded
+}
+
+impl<K, V: Keyed + Encode> fmt::Display for ExtendibleHash<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut hash_string = String::new();
+        let left_padding = (self.global_depth + 2) as usize;
+        let pad = " ".repeat(left_padding);
+        let small_square_sep = "+---+";
+        let big_square_size = 7;
+        let big_square_sep = format!("+{}+", "-".repeat(big_square_size));
+        let table_len = left_padding + big_square_size + 4;
+
+        // Print global depth
+        hash_string.push_str(format!("{pad}{small_square_sep}\n").as_str());
+        hash_string.push_str(format!("{pad}|{: ^3}|\n", self.global_depth).as_str());
+        hash_string.push_str(
+            format!(
+                "{pad}{small_square_sep}{}\n",
+                big_square_sep.get(5..).unwrap()
+            )
+            .as_str(),
+        );
+
+        // Print directories
+        for (i, d) in self.directory.iter().enumerate() {
+            hash_string.push_str(
+                format!(
+                    " {num:0length$b} |",
+                    num = i,
+                    length = self.global_depth as usize
+                )
+                .as_str(),
+            );
+            hash_string.push_str(
+                format!(
+                    "{: ^big_square_size$}|\n{pad}{big_square_sep}\n",
+                    self.buckets[*d].label()
+                )
+                .as_str(),
+            )
+        }
+
+        let mut bucket_string = String::new();
+
+        let overflow: Vec<usize> = self.buckets.iter().filter_map(|b| b.overflow).collect();
+
+        for (i, b) in self.buckets.iter().enumerate() {
+            if self.free.contains(&i) || overflow.contains(&i) {
+                continue;
+            }
+
+            // Baldes de overflow sao desenhados ao lado do balde primario
+            let mut lines: Vec<String> = b.to_string().lines().map(String::from).collect();
+
+            for o in self.chain(i).skip(1) {
+                let width = lines.iter().map(|l| l.len()).max().unwrap();
+
+                for (j, l) in self.buckets[o].to_string().lines().enumerate() {
+                    let arrow = if j == 3 { " -> " } else { "    " };
+                    lines[j] = format!("{:width$}{arrow}{l}", lines[j]);
+                }
+            }
+
+            bucket_string.push_str(format!("{}\n\n", lines.join("\n")).as_str())
+        }
+
+        let mut hash_lines: Vec<&str> = hash_string.lines().collect();
+        let mut buck_lines: Vec<&str> = bucket_string.lines().collect();
+
+        let right_pad = " ".repeat(table_len);
+
+        if hash_lines.len() < buck_lines.len() {
+            hash_lines.resize(buck_lines.len(), right_pad.as_str());
+        } else if buck_lines.len() < hash_lines.len() {
+            buck_lines.resize(hash_lines.len(), " ");
+        }
+
+        for (h, b) in hash_lines.iter().zip(buck_lines) {
+            let right_pad = table_len - h.len();
+            writeln!(f, "{h}{}{b}", " ".repeat(right_pad))?;
+        }
+
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        cell::Cell,
+        hash::{BuildHasherDefault, DefaultHasher},
+        io::{self, Cursor, Read},
+        rc::Rc,
+    };
+
+    use crate::{
+        entry::Entry,
+        error::Error,
+        extendible_hash::Rid,
+        hash_alt1::HashAlt1,
+        hash_alt2::HashAlt2,
+        hash_function::{self, Custom, FxHash},
+        record::Record,
+    };
+
+    #[test]
+    fn test_hash_display_global_depth_2() {
+        let h = HashAlt1::new(2, 4).unwrap();
+
+        println!("{h}");
+
+        assert_eq!(format!("{h}"), "    +---+      +---+\n    | 2 |      | 2 |\n    +---+---+  +---+---+---+---+\n 00 |   A   |  |   |   |   |   | A\n    +-------+  +---+---+---+---+\n 01 |   B   |  \n    +-------+  +---+\n 10 |   C   |  | 2 |\n    +-------+  +---+---+---+---+\n 11 |   D   |  |   |   |   |   | B\n    +-------+  +---+---+---+---+\n               \n               +---+\n               | 2 |\n               +---+---+---+---+\n               |   |   |   |   | C\n               +---+---+---+---+\n               \n               +---+\n               | 2 |\n               +---+---+---+---+\n               |   |   |   |   | D\n               +---+---+---+---+\n               \n\n");
+    }
+
+    #[test]
+    fn test_hash_display_global_depth_3() {
+        let h = HashAlt1::new(3, 4).unwrap();
+
+        println!("{h}");
+
+        assert_eq!(format!("{h}"), "     +---+      +---+\n     | 3 |      | 3 |\n     +---+---+  +---+---+---+---+\n 000 |   A   |  |   |   |   |   | A\n     +-------+  +---+---+---+---+\n 001 |   B   |  \n     +-------+  +---+\n 010 |   C   |  | 3 |\n     +-------+  +---+---+---+---+\n 011 |   D   |  |   |   |   |   | B\n     +-------+  +---+---+---+---+\n 100 |   E   |  \n     +-------+  +---+\n 101 |   F   |  | 3 |\n     +-------+  +---+---+---+---+\n 110 |   G   |  |   |   |   |   | C\n     +-------+  +---+---+---+---+\n 111 |   H   |  \n     +-------+  +---+\n                | 3 |\n                +---+---+---+---+\n                |   |   |   |   | D\n                +---+---+---+---+\n                \n                +---+\n                | 3 |\n                +---+---+---+---+\n                |   |   |   |   | E\n                +---+---+---+---+\n                \n                +---+\n                | 3 |\n                +---+---+---+---+\n                |   |   |   |   | F\n                +---+---+---+---+\n                \n                +---+\n                | 3 |\n                +---+---+---+---+\n                |   |   |   |   | G\n                +---+---+---+---+\n                \n                +---+\n                | 3 |\n                +---+---+---+---+\n                |   |   |   |   | H\n                +---+---+---+---+\n                \n\n");
+    }
+
+    #[test]
+    fn test_hash_display_global_depth_4() {
+        let h = HashAlt1::new(4, 4).unwrap();
+
+        println!("{h}");
+
+        assert_eq!(format!("{h}"), "      +---+      +---+\n      | 4 |      | 4 |\n      +---+---+  +---+---+---+---+\n 0000 |   A   |  |   |   |   |   | A\n      +-------+  +---+---+---+---+\n 0001 |   B   |  \n      +-------+  +---+\n 0010 |   C   |  | 4 |\n      +-------+  +---+---+---+---+\n 0011 |   D   |  |   |   |   |   | B\n      +-------+  +---+---+---+---+\n 0100 |   E   |  \n      +-------+  +---+\n 0101 |   F   |  | 4 |\n      +-------+  +---+---+---+---+\n 0110 |   G   |  |   |   |   |   | C\n      +-------+  +---+---+---+---+\n 0111 |   H   |  \n      +-------+  +---+\n 1000 |   I   |  | 4 |\n      +-------+  +---+---+---+---+\n 1001 |   J   |  |   |   |   |   | D\n      +-------+  +---+---+---+---+\n 1010 |   K   |  \n      +-------+  +---+\n 1011 |   L   |  | 4 |\n      +-------+  +---+---+---+---+\n 1100 |   M   |  |   |   |   |   | E\n      +-------+  +---+---+---+---+\n 1101 |   N   |  \n      +-------+  +---+\n 1110 |   O   |  | 4 |\n      +-------+  +---+---+---+---+\n 1111 |   P   |  |   |   |   |   | F\n      +-------+  +---+---+---+---+\n                 \n                 +---+\n                 | 4 |\n                 +---+---+---+---+\n                 |   |   |   |   | G\n                 +---+---+---+---+\n                 \n                 +---+\n                 | 4 |\n                 +---+---+---+---+\n                 |   |   |   |   | H\n                 +---+---+---+---+\n                 \n                 +---+\n                 | 4 |\n                 +---+---+---+---+\n                 |   |   |   |   | I\n                 +---+---+---+---+\n                 \n                 +---+\n                 | 4 |\n                 +---+---+---+---+\n                 |   |   |   |   | J\n                 +---+---+---+---+\n                 \n                 +---+\n                 | 4 |\n                 +---+---+---+---+\n                 |   |   |   |   | K\n                 +---+---+---+---+\n                 \n                 +---+\n                 | 4 |\n                 +---+---+---+---+\n                 |   |   |   |   | L\n                 +---+---+---+---+\n                 \n                 +---+\n                 | 4 |\n                 +---+---+---+---+\n                 |   |   |   |   | M\n                 +---+---+---+---+\n                 \n                 +---+\n                 | 4 |\n                 +---+---+---+---+\n                 |   |   |   |   | N\n                 +---+---+---+---+\n                 \n                 +---+\n                 | 4 |\n                 +---+---+---+---+\n                 |   |   |   |   | O\n                 +---+---+---+---+\n                 \n                 +---+\n                 | 4 |\n                 +---+---+---+---+\n                 |   |   |   |   | P\n                 +---+---+---+---+\n                 \n\n");
+    }
+
+    #[test]
+    fn test_hash_fun_2() {
+        let h = HashAlt1::new(2, 4).unwrap();
+
+        assert_eq!(h.hash_fun(&343), 3)
+    }
+
+    #[test]
+    fn test_hash_fun_3() {
+        let h = HashAlt1::new(3, 4).unwrap();
+
+        assert_eq!(h.hash_fun(&343), 7)
+    }
+
+    #[test]
+    fn test_insert_global_depth_2() {
+        let mut h = HashAlt1::new(2, 4).unwrap();
+
+        println!("{h}");
+
+        h.insert(Record{nseq: 2, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Duis quis fringilla diam. Duis in est.".to_string()}).unwrap();
+        h.insert(Record{nseq: 10, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Duis quis fringilla diam. Duis in est.".to_string()}).unwrap();
+        h.insert(Record{nseq: 102, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Duis quis fringilla diam. Duis in est.".to_string()}).unwrap();
+        h.insert(Record{nseq: 98, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Duis quis fringilla diam. Duis in est.".to_string()}).unwrap();
+        h.insert(Record{nseq: 118, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Duis quis fringilla diam. Duis in est.".to_string()}).unwrap();
+
+        println!("{h}");
+
+        h.insert(Record{nseq: 0, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Duis quis fringilla diam. Duis in est.".to_string()}).unwrap();
+        h.insert(Record{nseq: 4, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Duis quis fringilla diam. Duis in est.".to_string()}).unwrap();
+        h.insert(Record{nseq: 12, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Duis quis fringilla diam. Duis in est.".to_string()}).unwrap();
+        h.insert(Record{nseq: 20, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Duis quis fringilla diam. Duis in est.".to_string()}).unwrap();
+        h.insert(Record{nseq: 24, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Duis quis fringilla diam. Duis in est.".to_string()}).unwrap();
+
+        println!("{h}");
+
+        let s1 = h.search(&0).unwrap();
+        assert_eq!(*h.get(s1).unwrap(), Record{nseq: 0_i32, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Duis quis fringilla diam. Duis in est.".to_string()});
+        assert!(h.search(&14).is_err())
+    }
+
+    #[test]
+    fn test_hash_double_directory() {
+        let mut h = HashAlt1::new(2, 4).unwrap();
+
+        println!("{h}");
+
+        h.double_directory();
+
+        println!("{h}");
+
+        assert_eq!(1, 1);
+    }
+
+    #[test]
+    fn test_insert_split_double() {
+        let mut h = HashAlt1::new(2, 4).unwrap();
+
+        // 0, 8, 24, 56, 120
+
+        h.insert(Record{nseq: 0, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        h.insert(Record{nseq: 8, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        h.insert(Record{nseq: 24, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        h.insert(Record{nseq: 56, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+
+        println!("{h}");
+
+        h.insert(Record{nseq: 120, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+
+        println!("{h}");
+
+        assert_eq!(h.check_invariants(), Ok(()));
+
+        for nseq in [0, 8, 24, 56, 120] {
+            assert!(h.search(&nseq).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_split_bucket_with_many_pointers() {
+        let mut h = HashAlt1::new(1, 2).unwrap();
+
+        // Leva os pares ate gd = 3 enquanto o balde dos impares fica com ld = 1
+        for nseq in [0, 2, 4, 8, 1, 3] {
+            h.insert(Record{nseq, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        }
+
+        assert_eq!(h.global_depth, 3);
+        assert_eq!(h.buckets[h.directory[1]].local_depth, 1);
+
+        h.insert(Record{nseq: 5, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+
+        println!("{h}");
+
+        assert_eq!(h.check_invariants(), Ok(()));
+        assert_eq!(h.directory[0b001], h.directory[0b101]);
+        assert_eq!(h.directory[0b011], h.directory[0b111]);
+        assert_ne!(h.directory[0b001], h.directory[0b011]);
+
+        for nseq in [0, 2, 4, 8, 1, 3, 5] {
+            assert!(h.search(&nseq).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_invariants_random_workload() {
+        // Permutacao de 0..=300
+        let keys: Vec<i32> = (0..=300).map(|i| (i * 7919) % 301).collect();
+
+        for hf in [hash_function::LOW_BITS_ID, hash_function::FX_HASH_ID] {
+            let mut h =
+                HashAlt1::with_hash_function(1, 4, hash_function::from_id(hf).unwrap()).unwrap();
+
+            for nseq in &keys {
+                h.insert(Record {
+                    nseq: *nseq,
+                    text: "Lorem ipsum".to_string(),
+                })
+                .unwrap();
+                assert_eq!(h.check_invariants(), Ok(()));
+            }
+
+            for nseq in &keys {
+                assert!(h.search(nseq).is_ok());
+            }
+
+            for nseq in keys.iter().step_by(2) {
+                assert!(h.remove(nseq).is_ok());
+                assert_eq!(h.check_invariants(), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_serialize_hash() {
+        let mut h = HashAlt1::new(2, 4).unwrap();
+
+        h.insert(Record{nseq: 0, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        h.insert(Record{nseq: 1, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        h.insert(Record{nseq: 2, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        h.insert(Record{nseq: 3, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+
+        let encoded = h.serialize().unwrap();
+
+        assert_eq!(
+            encoded.len(),
+            6 + 1 + 1 + 1 + 4 + 8 + 4 + 4 + 4 + 4 + 4 * (21 + 4 * (17 + 100) + 4) + 8 + 4 * 4 + 4
+        )
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let mut h = HashAlt1::new(2, 4).unwrap();
+
+        for nseq in [0, 1, 2, 3, 4, 8] {
+            h.insert(Record{nseq, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        }
+
+        let encoded = h.serialize().unwrap();
+        let h2 = HashAlt1::read_from(&mut &encoded[..]).unwrap();
+
+        println!("{h2}");
+
+        assert_eq!(format!("{h2}"), format!("{h}"));
+        assert_eq!(h2.search(&8).unwrap(), h.search(&8).unwrap());
+    }
+
+    /// Reader that returns one byte per call, like a slow pipe.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_read_from_short_reads() {
+        let mut h = HashAlt1::new(1, 2).unwrap();
+
+        for nseq in 0..20 {
+            h.insert(Record {
+                nseq,
+                text: "Lorem ipsum ".repeat(nseq as usize),
+            })
+            .unwrap();
+        }
+
+        let encoded = h.serialize().unwrap();
+        let mut r = Trickle(&encoded);
+        let h2 = HashAlt1::read_from(&mut r).unwrap();
+
+        assert!(r.0.is_empty());
+        assert_eq!(format!("{h2}"), format!("{h}"));
+        assert_eq!(h2.serialize().unwrap(), encoded);
+    }
+
+    fn legacy_table() -> Vec<u8> {
+        let records = |nseqs: &[i32]| {
+            nseqs
+                .iter()
+                .map(|nseq| Record {
+                    nseq: *nseq,
+                    text: format!("registro {nseq}"),
+                })
+                .collect()
+        };
+
+        // O balde B tem ld 1 e dois ponteiros
+        super::legacy_image(
+            2,
+            4,
+            &[
+                (2, records(&[0, 4, 8])),
+                (1, records(&[1, 3, 5, 7])),
+                (2, records(&[2, 6])),
+            ],
+            &[0, 1, 2, 1],
+        )
+    }
+
+    #[test]
+    fn test_read_legacy() {
+        let bytes = legacy_table();
+        let mut h = HashAlt1::read_from(&mut &bytes[..]).unwrap();
+
+        assert_eq!(h.check_invariants(), Ok(()));
+        assert_eq!(h.len(), 9);
+        assert!(h.global_depth() >= 2);
+
+        for nseq in 0..9 {
+            let rid = h.search(&nseq).unwrap();
+            assert_eq!(h.get(rid).unwrap().text, format!("registro {nseq}"));
+        }
+
+        // Salvo de novo no formato atual
+        h.insert(Record {
+            nseq: 9,
+            text: "novo".to_string(),
+        })
+        .unwrap();
+
+        let encoded = h.serialize().unwrap();
+
+        assert_eq!(encoded[..4], *b"EXHT");
+        assert_eq!(HashAlt1::read_from(&mut &encoded[..]).unwrap().len(), 10);
+
+        // Diretorio apontando para um balde que nao existe
+        let mut bad = bytes.clone();
+        let last = bad.len() - 1;
+        bad[last] = 3;
+
+        assert!(matches!(
+            HashAlt1::read_from(&mut &bad[..]),
+            Err(Error::Corrupt { .. })
+        ));
+
+        // Cortado no meio de um balde
+        assert!(matches!(
+            HashAlt1::read_from(&mut &bytes[..200]),
+            Err(Error::Corrupt { .. })
+        ));
+
+        // Os indices desses arquivos nao tinham rids
+        assert!(matches!(
+            HashAlt2::read_from(&mut &bytes[..]),
+            Err(Error::WrongFormat { .. })
+        ));
+    }
+
+    #[test]
+    fn test_insert_negative_key() {
+        let mut h = HashAlt1::new(2, 4).unwrap();
+
+        h.insert(Record{nseq: -5, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+
+        assert_eq!(h.hash_fun(&-5), 3);
+        assert!(h.search(&-5).is_ok());
+    }
+
+    #[test]
+    fn test_deserialize_keeps_hash_function() {
+        let mut h = HashAlt1::with_hash_function(1, 4, Box::new(FxHash)).unwrap();
+
+        for nseq in [0, 8, 24, 56, 120, 248] {
+            h.insert(Record{nseq, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        }
+
+        let mut encoded = Vec::new();
+        h.write_to(&mut encoded).unwrap();
+
+        let h2 = HashAlt1::read_from(&mut Cursor::new(encoded)).unwrap();
+
+        for nseq in [0, 8, 24, 56, 120, 248] {
+            assert_eq!(h2.search(&nseq).unwrap(), h.search(&nseq).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_deserialize_custom_hash_function() {
+        let mut h = HashAlt1::with_hash_function(
+            2,
+            4,
+            Box::new(Custom(BuildHasherDefault::<DefaultHasher>::default())),
+        )
+        .unwrap();
+
+        for nseq in 0..10 {
+            h.insert(Record{nseq, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        }
+
+        let encoded = h.serialize().unwrap();
+        let h2 = HashAlt1::read_from_with_hash_function(
+            &mut &encoded[..],
+            Box::new(Custom(BuildHasherDefault::<DefaultHasher>::default())),
+        )
+        .unwrap();
+
+        for nseq in 0..10 {
+            assert_eq!(h2.search(&nseq).unwrap(), h.search(&nseq).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_remove_merge_halve() {
+        let mut h = HashAlt1::new(1, 2).unwrap();
+
+        for nseq in [0, 1, 2, 4] {
+            h.insert(Record{nseq, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        }
+
+        assert_eq!(h.global_depth, 2);
+        assert_eq!(h.buckets.len(), 3);
+
+        h.remove(&4).unwrap();
+
+        println!("{h}");
+
+        assert_eq!(h.global_depth, 1);
+        assert_eq!(h.buckets.len(), 2);
+        assert!(h.search(&2).is_ok());
+
+        for nseq in [0, 1, 2] {
+            assert!(h.remove(&nseq).is_ok());
+        }
+
+        assert_eq!(h.global_depth, 0);
+        assert_eq!(h.directory, vec![0]);
+        assert_eq!(h.buckets.len(), 1);
+    }
+
+    #[test]
+    fn test_remove_merge_threshold() {
+        let mut h = HashAlt1::new(1, 2).unwrap();
+        h.set_merge_threshold(0.0).unwrap();
+
+        for nseq in [0, 1, 2, 4] {
+            h.insert(Record{nseq, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        }
+
+        h.remove(&4).unwrap();
+
+        assert_eq!(h.global_depth, 2);
+
+        h.remove(&2).unwrap();
+
+        assert_eq!(h.global_depth, 2);
+
+        h.remove(&0).unwrap();
+
+        assert_eq!(h.global_depth, 1);
+        assert_eq!(h.buckets.len(), 2);
+    }
+
+    #[test]
+    fn test_merge_threshold_range() {
+        let mut h = HashAlt1::new(1, 2).unwrap();
+
+        for threshold in [1.5, -0.5, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                h.set_merge_threshold(threshold),
+                Err(Error::MergeThreshold(_))
+            ));
+        }
+
+        assert_eq!(h.merge_threshold, 1.0);
+        h.set_merge_threshold(0.5).unwrap();
+        assert_eq!(h.merge_threshold, 0.5);
+    }
+
+    #[test]
+    fn test_split_reuses_freed_bucket() {
+        let mut h = HashAlt1::new(2, 2).unwrap();
+
+        for nseq in [0, 4, 8] {
+            h.insert(Record{nseq, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        }
+
+        let n = h.buckets.len();
+
+        h.remove(&8).unwrap();
+        h.remove(&4).unwrap();
+        h.insert(Record{nseq: 4, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        h.insert(Record{nseq: 8, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+
+        assert!(h.buckets.len() <= n);
+        assert!(h.search(&8).is_ok());
+    }
+
+    #[test]
+    fn test_overflow_chain() {
+        let mut h = HashAlt1::new(1, 2).unwrap();
+        h.set_max_global_depth(2).unwrap();
+
+        // Todas as chaves terminam em 00
+        for nseq in [0, 4, 8, 12, 16, 1] {
+            h.insert(Record{nseq, text: "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Quisque imperdiet lacinia orci aliquam.".to_string()}).unwrap();
+        }
+
+        println!("{h}");
+
+        assert_eq!(h.global_depth, 2);
+        assert_eq!(h.check_invariants(), Ok(()));
+        assert_eq!(h.chain(h.directory[0]).count(), 3);
+        assert!(format!("{h}").contains(" -> "));
+
+        for nseq in [0, 4, 8, 12, 16, 1] {
+            assert!(h.search(&nseq).is_ok());
+        }
+
+        let encoded = h.serialize().unwrap();
+        let h2 = HashAlt1::read_from(&mut &encoded[..]).unwrap();
+
+        assert_eq!(h2.check_invariants(), Ok(()));
+        assert_eq!(format!("{h2}"), format!("{h}"));
+
+        for nseq in [16, 12, 8] {
+            assert!(h.remove(&nseq).is_ok());
+            assert_eq!(h.check_invariants(), Ok(()));
+        }
+
+        assert_eq!(h.chain(h.directory[0]).count(), 1);
+        assert!(h.search(&4).is_ok());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut h = HashAlt1::new(2, 4).unwrap();
+
+        h.insert(Record {
+            nseq: 1,
+            text: "Lorem ipsum".to_string(),
+        })
+        .unwrap();
+
+        assert!(matches!(
+            h.insert(Record {
+                nseq: 1,
+                text: "Lorem ipsum".to_string()
+            }),
+            Err(Error::DuplicateKey(_))
+        ));
+        assert!(matches!(h.remove(&2), Err(Error::KeyNotFound(_))));
+        assert!(matches!(h.search(&2), Err(Error::KeyNotFound(_))));
+        assert!(matches!(
+            h.set_max_global_depth(33),
+            Err(Error::DepthLimit(33))
+        ));
+
+        // Sem estourar o shift nem alocar o diretorio
+        assert!(matches!(HashAlt1::new(16, 2), Err(Error::DepthLimit(16))));
+        assert!(matches!(HashAlt1::new(32, 2), Err(Error::DepthLimit(32))));
+        assert!(matches!(HashAlt1::new(33, 2), Err(Error::DepthLimit(33))));
+        assert!(matches!(HashAlt1::new(64, 2), Err(Error::DepthLimit(64))));
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut h = HashAlt1::new(1, 2).unwrap();
+        h.set_max_global_depth(2).unwrap();
+
+        // Balde dos impares com ld = 1 e dois ponteiros, mais uma cadeia de overflow
+        for nseq in [0, 4, 8, 12, 16, 1, 3] {
+            h.insert(Record {
+                nseq,
+                text: "Lorem ipsum".to_string(),
+            })
+            .unwrap();
+        }
+
+        let mut keys: Vec<i32> = h.keys().copied().collect();
+        keys.sort();
+
+        assert_eq!(keys, vec![0, 1, 3, 4, 8, 12, 16]);
+        assert_eq!(h.len(), 7);
+        assert_eq!(h.values().count(), 7);
+        assert_eq!((&h).into_iter().count(), 7);
+
+        for r in h.iter_mut() {
+            r.text.push('!');
+        }
+
+        assert!(h.iter().all(|r| r.text == "Lorem ipsum!"));
+
+        let mut records: Vec<Record> = h.into_iter().collect();
+        records.sort_by_key(|r| r.nseq);
+
+        assert_eq!(records.len(), 7);
+        assert_eq!(records[6].nseq, 16);
+    }
+
+    #[test]
+    fn test_retain_drain() {
+        let mut h: HashAlt1 = (0..20)
+            .map(|nseq| Record {
+                nseq,
+                text: "Lorem ipsum".to_string(),
+            })
+            .collect();
+
+        assert_eq!(h.len(), 20);
+        assert_eq!(h.check_invariants(), Ok(()));
+
+        h.retain(|r| r.nseq % 3 == 0);
+
+        let mut keys: Vec<i32> = h.keys().copied().collect();
+        keys.sort();
+
+        assert_eq!(keys, vec![0, 3, 6, 9, 12, 15, 18]);
+        assert_eq!(h.check_invariants(), Ok(()));
+
+        h.extend([21, 24].map(|nseq| Record {
+            nseq,
+            text: "Lorem ipsum".to_string(),
+        }));
+
+        assert_eq!(h.drain().count(), 9);
+        assert!(h.is_empty());
+        assert_eq!(h.global_depth, 0);
+        assert_eq!(h.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut h = HashAlt1::new(1, 2).unwrap();
+
+        for nseq in 0..6 {
+            h.entry(nseq)
+                .or_insert(Record {
+                    nseq,
+                    text: "Lorem ipsum".to_string(),
+                })
+                .unwrap();
+        }
+
+        let rid = h.search(&4).unwrap();
+
+        h.entry(4)
+            .and_modify(|r| r.text = "dolor".to_string())
+            .unwrap()
+            .or_insert_with(|| unreachable!())
+            .unwrap();
+
+        assert_eq!(h.search(&4).unwrap(), rid);
+        assert_eq!(h.get(rid).unwrap().text, "dolor");
+        assert_eq!(h.len(), 6);
+
+        let r = h
+            .entry(6)
+            .and_modify(|_| unreachable!())
+            .unwrap()
+            .or_insert_with(|| Record {
+                nseq: 6,
+                text: "sit".to_string(),
+            })
+            .unwrap();
+
+        assert_eq!(r.text, "sit");
+        drop(r);
+
+        assert!(matches!(h.entry(6), Entry::Occupied(_)));
+        assert!(matches!(h.entry(7), Entry::Vacant(_)));
+        assert_eq!(h.check_invariants(), Ok(()));
+
+        // Chave errada e erro, nao panic
+        let Entry::Occupied(mut e) = h.entry(6) else {
+            unreachable!()
+        };
+        assert!(matches!(
+            e.insert(Record {
+                nseq: 7,
+                text: "sit".to_string(),
+            }),
+            Err(Error::KeyChanged(_))
+        ));
+        assert!(matches!(
+            h.entry(7).or_insert(Record {
+                nseq: 8,
+                text: "sit".to_string(),
+            }),
+            Err(Error::KeyChanged(_))
+        ));
+
+        let Entry::Occupied(e) = h.entry(6) else {
+            unreachable!()
+        };
+        assert_eq!(e.remove().unwrap().text, "sit");
+        assert_eq!(h.len(), 6);
+    }
+
+    #[test]
+    fn test_entry_key_change() {
+        let mut h = HashAlt1::new(1, 2).unwrap();
+
+        for nseq in 0..6 {
+            h.insert(Record {
+                nseq,
+                text: "Lorem ipsum".to_string(),
+            })
+            .unwrap();
+        }
+
+        let rid = h.search(&4).unwrap();
+
+        // A chave nova vai para o balde dela com o mesmo rid
+        h.entry(4).and_modify(|r| r.nseq = 9).unwrap();
+
+        assert_eq!(h.search(&9).unwrap(), rid);
+        assert!(h.search(&4).is_err());
+        assert_eq!(h.check_invariants(), Ok(()));
+
+        // Chave ocupada: nada muda
+        assert!(matches!(
+            h.entry(9).and_modify(|r| r.nseq = 1),
+            Err(Error::DuplicateKey(_))
+        ));
+        assert_eq!(h.search(&9).unwrap(), rid);
+        assert_eq!(h.len(), 6);
+
+        if let Entry::Occupied(mut e) = h.entry(9) {
+            e.get_mut().nseq = 10;
+            assert_eq!(e.key(), &10);
+            e.into_mut().nseq = 2;
+        }
+
+        assert_eq!(h.search(&10).unwrap(), rid);
+        assert_eq!(h.get(rid).unwrap().nseq, 10);
+        assert_eq!(h.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_update_upsert_replace() {
+        let mut h = HashAlt1::new(1, 2).unwrap();
+
+        for nseq in 0..6 {
+            h.insert(Record {
+                nseq,
+                text: "Lorem ipsum".to_string(),
+            })
+            .unwrap();
+        }
+
+        let rid = h.search(&3).unwrap();
+
+        let old = h
+            .replace(Record {
+                nseq: 3,
+                text: "dolor".to_string(),
+            })
+            .unwrap();
+
+        assert_eq!(old.text, "Lorem ipsum");
+        assert_eq!(h.search(&3).unwrap(), rid);
+
+        h.update(&3, |r| r.text.push_str(" sit")).unwrap();
+
+        assert_eq!(h.get(rid).unwrap().text, "dolor sit");
+        assert!(matches!(
+            h.update(&3, |r| r.nseq = 30),
+            Err(Error::KeyChanged(_))
+        ));
+        assert_eq!(h.get(rid).unwrap().text, "dolor sit");
+        assert!(matches!(
+            h.replace(Record {
+                nseq: 9,
+                text: "amet".to_string()
+            }),
+            Err(Error::KeyNotFound(_))
+        ));
+
+        let up = |nseq| Record {
+            nseq,
+            text: "amet".to_string(),
+        };
+
+        assert_eq!(h.upsert(up(3)).unwrap().unwrap().text, "dolor sit");
+        assert_eq!(h.search(&3).unwrap(), rid);
+        assert!(h.upsert(up(9)).unwrap().is_none());
+        assert_eq!(h.len(), 7);
+        assert_eq!(h.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_stable_rids() {
+        let mut h = HashAlt1::new(1, 2).unwrap();
+        let moves = Rc::new(Cell::new(0));
+        let m = moves.clone();
+
+        h.set_relocation_hook(move |_, _| m.set(m.get() + 1));
+
+        let rids: Vec<(i32, Rid)> = (0..40)
+            .map(|nseq| {
+                let rid = h
+                    .insert(Record {
+                        nseq,
+                        text: "Lorem ipsum".to_string(),
+                    })
+                    .unwrap();
+                (nseq, rid)
+            })
+            .collect();
+
+        // Os splits movem registros ja inseridos
+        assert!(moves.get() > 40);
+
+        for (nseq, _) in rids.iter().step_by(3) {
+            h.remove(nseq).unwrap();
+        }
+
+        assert_eq!(h.check_invariants(), Ok(()));
+
+        for (nseq, rid) in &rids {
+            if nseq % 3 == 0 {
+                assert!(h.get(*rid).is_none());
+            } else {
+                assert_eq!(h.get(*rid).unwrap().nseq, *nseq);
+                assert_eq!(h.search(nseq).unwrap(), *rid);
+            }
+        }
+
+        let encoded = h.serialize().unwrap();
+        let mut h2 = HashAlt1::read_from(&mut &encoded[..]).unwrap();
+
+        assert_eq!(h2.check_invariants(), Ok(()));
+
+        for (nseq, rid) in rids.iter().filter(|(n, _)| n % 3 != 0) {
+            assert_eq!(h2.get(*rid).unwrap().nseq, *nseq);
+        }
+
+        let rid = h2
+            .insert(Record {
+                nseq: 100,
+                text: "Lorem ipsum".to_string(),
+            })
+            .unwrap();
+
+        assert!(rids.iter().all(|(_, r)| *r != rid));
+    }
+
+    #[test]
+    fn test_deserialize_corrupt() {
+        let mut h = HashAlt1::new(2, 4).unwrap();
+
+        h.insert(Record {
+            nseq: 1,
+            text: "Lorem ipsum".to_string(),
+        })
+        .unwrap();
+
+        let encoded = h.serialize().unwrap();
+
+        assert!(matches!(
+            HashAlt1::read_from(&mut &encoded[..100]),
+            Err(Error::Corrupt { offset: 37, .. })
+        ));
+
+        let mut encoded = encoded;
+        encoded[8] = 99;
+
+        assert!(matches!(
+            HashAlt1::read_from(&mut &encoded[..]),
+            Err(Error::Corrupt { offset: 9, .. })
+        ));
+    }
+
+    #[test]
+    fn test_format_errors() {
+        let mut h = HashAlt1::new(1, 2).unwrap();
+
+        for nseq in 0..4 {
+            h.insert(Record {
+                nseq,
+                text: "Lorem ipsum".to_string(),
+            })
+            .unwrap();
+        }
+
+        let encoded = h.serialize().unwrap();
+
+        // Le `bytes` como HashAlt1 com um byte trocado
+        let load = |i: usize, byte: u8| {
+            let mut bytes = encoded.clone();
+            bytes[i] = byte;
+
+            HashAlt1::read_from(&mut &bytes[..])
+        };
+
+        assert!(matches!(
+            load(0, b'X'),
+            Err(Error::WrongFormat {
+                field: "numero magico",
+                ..
+            })
+        ));
+        assert!(matches!(
+            load(4, 9),
+            Err(Error::WrongFormat {
+                field: "versao",
+                ..
+            })
+        ));
+        assert!(matches!(
+            load(5, 2),
+            Err(Error::WrongFormat { field: "tipo", .. })
+        ));
+        assert!(matches!(
+            load(11, 1),
+            Err(Error::Checksum { offset: 0, .. })
+        ));
+
+        // Primeiro balde: cabecalho (29B), #overflow (4B), #baldes (4B)
+        match load(37 + 2, b'Z') {
+            Err(Error::Checksum { offset: 37, part }) => assert_eq!(part, "balde 0"),
+            r => panic!("{:?}", r.err()),
+        }
+
+        let last = encoded.len() - 5;
+        match load(last, 7) {
+            Err(Error::Checksum { part, .. }) => assert_eq!(part, "diretorio"),
+            r => panic!("{:?}", r.err()),
+        }
+
+        // Um indice secundario nao e lido como primario
+        let mut h2 = HashAlt2::new(1, 2).unwrap();
+        h2.insert((("Lorem ipsum".to_string(), 0), 0)).unwrap();
+        match HashAlt1::read_from(&mut &h2.serialize().unwrap()[..]) {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Arquivo com tipo IndexEntry, esperado Record"
+            ),
+            Ok(_) => panic!("HashAlt2 lido como HashAlt1"),
+        }
+
+        // Funcao de hash diferente da usada para escrever
+        assert!(matches!(
+            HashAlt1::read_from_with_hash_function(&mut &encoded[..], Box::new(FxHash)),
+            Err(Error::WrongFormat {
+                field: "funcao de hash",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_variable_length_records() {
+        let mut h = HashAlt1::new(0, 2).unwrap();
+
+        // Cabem mais que 2 registros curtos numa pagina planejada para 2
+        for nseq in 0..8 {
+            h.insert(Record {
+                nseq,
+                text: "curto".to_string(),
+            })
+            .unwrap();
+        }
+
+        assert_eq!(h.global_depth, 0);
+        assert_eq!(h.buckets[0].data.len(), 8);
+
+        // Maior que a pagina: vai para paginas de overflow
+        h.insert(Record {
+            nseq: 100,
+            text: "Lorem ipsum ".repeat(200),
+        })
+        .unwrap();
+        h.update(&3, |r| r.text = "dolor sit amet ".repeat(20))
+            .unwrap();
+
+        assert_eq!(h.check_invariants(), Ok(()));
+
+        let encoded = h.serialize().unwrap();
+        let h2 = HashAlt1::read_from(&mut &encoded[..]).unwrap();
+
+        assert_eq!(h2.check_invariants(), Ok(()));
+        assert_eq!(h2.page_size(), h.page_size());
+
+        for r in h.iter() {
+            assert_eq!(h2.get(h2.search(&r.nseq).unwrap()), Some(r));
+        }
+    }
+
+    #[test]
+    fn test_large_counts() {
+        // Mais de 255 registros por balde e de 65535 baldes
+        let mut h = HashAlt1::new(0, 300).unwrap();
+
+        for nseq in 0..300 {
+            h.insert(Record {
+                nseq,
+                text: "Lorem ipsum".to_string(),
+            })
+            .unwrap();
+        }
+
+        assert_eq!(h.global_depth, 0);
+
+        let h2 = HashAlt1::read_from(&mut &h.serialize().unwrap()[..]).unwrap();
+
+        assert_eq!(h2.buckets[0].size, 300);
+        assert_eq!(h2.len(), 300);
+
+        let mut h = HashAlt1::new(15, 1).unwrap();
+
+        for nseq in [0, 30_000, 32_767] {
+            h.insert(Record {
+                nseq,
+                text: "Lorem ipsum".to_string(),
+            })
+            .unwrap();
+        }
+
+        let h2 = HashAlt1::read_from(&mut &h.serialize().unwrap()[..]).unwrap();
+
+        assert_eq!(h2.bucket_count(), 1 << 15);
+        assert_eq!(h2.directory[30_000], 30_000);
+        assert_eq!(h2.get(h2.search(&32_767).unwrap()).unwrap().nseq, 32_767);
+
+        // Depois de ZZ os baldes continuam com ids e nomes distintos
+        let labels: std::collections::HashSet<String> =
+            h2.buckets.iter().map(|b| b.label()).collect();
+
+        assert_eq!(h2.buckets[702].id(), 702);
+        assert_eq!(h2.buckets[702].label(), "AAA");
+        assert_eq!(labels.len(), 1 << 15);
+    }
+}
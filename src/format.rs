@@ -0,0 +1,102 @@
+use std::io::Read;
+
+use crate::{
+    error::{Error, Result},
+    extendible_hash::MAX_GLOBAL_DEPTH,
+    reader::Reader,
+};
+
+/// Version of the file formats written by this crate.
+pub const VERSION: u8 = 2;
+
+/// Version of the headerless files of the first releases, which only had
+/// tables of `Record`s and of `(text, nseq)` entries:
+/// `| 1B gd | 2B #baldes | baldes | 2B #direc | 2B d |...|`, every bucket
+/// `| 3B nome | 1B ld | 1B size | size * slot |` with fixed size slots.
+/// They are read by rebuilding the table from their records.
+pub const LEGACY_VERSION: u8 = 1;
+
+/// First bytes of a file written by `ExtendibleHash::serialize`.
+pub const TABLE_MAGIC: [u8; 4] = *b"EXHT";
+
+/// First bytes of a `DiskHash` file.
+pub const DISK_MAGIC: [u8; 4] = *b"EXHD";
+
+/// First bytes of the page file of a `Store`.
+pub const STORE_MAGIC: [u8; 4] = *b"EXHS";
+
+/// Name of a table kind (`Encode::KIND`) in error messages.
+pub fn kind_name(kind: u8) -> String {
+    match kind {
+        0 => "registro sem tipo".to_string(),
+        1 => "Record".to_string(),
+        2 => "IndexEntry".to_string(),
+        3 => "TextEntry".to_string(),
+        4 => "Row".to_string(),
+        k if k & 0xf0 == 0x10 => format!("KeyEntry {}", k & 0x0f),
+        k => format!("tipo {k}"),
+    }
+}
+
+/// `| 4B magic | 1B version | 1B kind |`
+pub(crate) fn write_header(magic: [u8; 4], kind: u8, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&magic);
+    buf.push(VERSION);
+    buf.push(kind);
+}
+
+/// Reads what `write_header` wrote, failing with `WrongFormat` if it is not
+/// a file of the given magic, version and kind.
+pub(crate) fn read_header<R: Read>(r: &mut Reader<R>, magic: [u8; 4], kind: u8) -> Result<()> {
+    let first = r.read_u8()?;
+
+    read_header_from(r, first, magic, kind)
+}
+
+/// Whether a file starting with the byte `first` is a headerless one of
+/// `LEGACY_VERSION` and not one with `magic`. Their first byte is the
+/// global depth, at most `MAX_GLOBAL_DEPTH`, and no magic starts that low.
+pub(crate) fn is_legacy(first: u8, magic: [u8; 4]) -> bool {
+    first != magic[0] && first <= MAX_GLOBAL_DEPTH
+}
+
+/// `read_header` for a file whose first byte was already read.
+pub(crate) fn read_header_from<R: Read>(
+    r: &mut Reader<R>,
+    first: u8,
+    magic: [u8; 4],
+    kind: u8,
+) -> Result<()> {
+    let mut found = [first, 0, 0, 0];
+    r.read_exact(&mut found[1..])?;
+
+    if found != magic {
+        return Err(Error::WrongFormat {
+            field: "numero magico",
+            expected: format!("{:?}", String::from_utf8_lossy(&magic)),
+            found: format!("{:?}", String::from_utf8_lossy(&found)),
+        });
+    }
+
+    let version = r.read_u8()?;
+
+    if version != VERSION {
+        return Err(Error::WrongFormat {
+            field: "versao",
+            expected: VERSION.to_string(),
+            found: version.to_string(),
+        });
+    }
+
+    let found = r.read_u8()?;
+
+    if found != kind {
+        return Err(Error::WrongFormat {
+            field: "tipo",
+            expected: kind_name(kind),
+            found: kind_name(found),
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,254 @@
+use std::hash::{BuildHasher, DefaultHasher, Hasher};
+
+pub const LOW_BITS_ID: u8 = 0;
+pub const FX_HASH_ID: u8 = 1;
+pub const SIP_HASH_ID: u8 = 2;
+pub const CUSTOM_ID: u8 = 255;
+
+/// Hash used by the directory. Only the low `global_depth` bits of the result are used.
+pub trait HashFunction {
+    /// Identifier stored in the serialized file.
+    fn id(&self) -> u8;
+
+    fn hasher(&self) -> Box<dyn Hasher>;
+}
+
+/// Returns the built-in hash function with the given id.
+pub fn from_id(id: u8) -> Option<Box<dyn HashFunction>> {
+    match id {
+        LOW_BITS_ID => Some(Box::new(LowBits)),
+        FX_HASH_ID => Some(Box::new(FxHash)),
+        SIP_HASH_ID => Some(Box::new(SipHash)),
+        _ => None,
+    }
+}
+
+/// The key itself: integers are taken as is, so the directory looks at the
+/// key's low bits. Whatever was written before the last field (the text of a
+/// `(text, nseq)` key, say) is mixed in, so every field changes the bucket.
+pub struct LowBits;
+
+impl HashFunction for LowBits {
+    fn id(&self) -> u8 {
+        LOW_BITS_ID
+    }
+
+    fn hasher(&self) -> Box<dyn Hasher> {
+        Box::new(LowBitsHasher(0))
+    }
+}
+
+struct LowBitsHasher(u64);
+
+impl LowBitsHasher {
+    fn add(&mut self, word: u64) {
+        // Com o estado zerado (chave de um campo so) a palavra passa intacta
+        self.0 = fmix64(self.0) ^ word;
+    }
+}
+
+impl Hasher for LowBitsHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.add(u64::from_le_bytes(word));
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.add(i as u64);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.add(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.add(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.add(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.add(i as u64);
+    }
+
+    fn write_i8(&mut self, i: i8) {
+        self.write_u8(i as u8)
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16)
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32)
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64)
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_usize(i as usize)
+    }
+}
+
+/// MurmurHash3 finalizer: every input bit changes about half of the output
+/// bits, and `fmix64(0) == 0`.
+fn fmix64(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+/// The multiply-rotate hash used by rustc. Cheap and mixes sequential and
+/// strided keys well.
+pub struct FxHash;
+
+impl HashFunction for FxHash {
+    fn id(&self) -> u8 {
+        FX_HASH_ID
+    }
+
+    fn hasher(&self) -> Box<dyn Hasher> {
+        Box::new(FxHasher(0))
+    }
+}
+
+struct FxHasher(u64);
+
+const FX_SEED: u64 = 0xf135_7aea_2e62_a9c5;
+
+impl FxHasher {
+    fn add(&mut self, word: u64) {
+        self.0 = self.0.wrapping_add(word).wrapping_mul(FX_SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn finish(&self) -> u64 {
+        // A multiplicacao joga a entropia nos bits altos, e o diretorio usa os baixos
+        self.0.rotate_left(26)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.add(u64::from_le_bytes(word));
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.add(i as u64);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.add(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.add(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.add(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.add(i as u64);
+    }
+}
+
+/// SipHash-1-3 with zero keys, as in `std`'s `DefaultHasher::new()`. The
+/// algorithm may change between Rust releases, so files written with it are
+/// only guaranteed to reload with the same toolchain.
+pub struct SipHash;
+
+impl HashFunction for SipHash {
+    fn id(&self) -> u8 {
+        SIP_HASH_ID
+    }
+
+    fn hasher(&self) -> Box<dyn Hasher> {
+        Box::new(DefaultHasher::new())
+    }
+}
+
+/// Any user supplied `BuildHasher`. It is stored as `CUSTOM_ID`, so a table
+/// using it must be reloaded with `read_from_with_hash_function`.
+pub struct Custom<S>(pub S);
+
+impl<S> HashFunction for Custom<S>
+where
+    S: BuildHasher,
+    S::Hasher: 'static,
+{
+    fn id(&self) -> u8 {
+        CUSTOM_ID
+    }
+
+    fn hasher(&self) -> Box<dyn Hasher> {
+        Box::new(self.0.build_hasher())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::hash::{BuildHasherDefault, Hash};
+
+    use super::*;
+
+    fn hash<K: Hash>(h: &dyn HashFunction, key: &K) -> u64 {
+        let mut hasher = h.hasher();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_low_bits() {
+        assert_eq!(hash(&LowBits, &343), 343);
+        assert_eq!(hash(&LowBits, &-1) & 0b111, 0b111);
+        assert_ne!(
+            hash(&LowBits, &("abc".to_string(), 343)) & 0b111,
+            hash(&LowBits, &("abd".to_string(), 343)) & 0b111
+        );
+    }
+
+    #[test]
+    fn test_fx_hash_spreads_strided_keys() {
+        let low_bits: Vec<u64> = [0, 8, 24, 56, 120]
+            .iter()
+            .map(|k| hash(&FxHash, k) & 0b111)
+            .collect();
+
+        assert!(low_bits.iter().any(|b| *b != low_bits[0]));
+    }
+
+    #[test]
+    fn test_custom() {
+        let h = Custom(BuildHasherDefault::<DefaultHasher>::default());
+
+        assert_eq!(h.id(), CUSTOM_ID);
+        assert_eq!(hash(&h, &343), hash(&SipHash, &343));
+    }
+
+    #[test]
+    fn test_from_id() {
+        for id in [LOW_BITS_ID, FX_HASH_ID, SIP_HASH_ID] {
+            assert_eq!(from_id(id).unwrap().id(), id);
+        }
+
+        assert!(from_id(CUSTOM_ID).is_none());
+    }
+}
@@ -0,0 +1,257 @@
+use std::io::{Read, Write};
+
+use crate::{
+    bucket,
+    error::{Error, Result},
+    extendible_hash::{ExtendibleHash, Rid},
+    hash_alt1::HashAlt1,
+    reader::Reader,
+    record::Record,
+    store::Store,
+    table::SecondaryIndex,
+    traits::{Encode, Keyed},
+};
+
+/// `(text, rid)`: text of a record and its rid in `HashAlt1`.
+pub type TextEntry = (String, Rid);
+
+/// Non-unique index: `text -> rid` for every record with that text.
+/// Duplicates of one text that do not fit in a bucket go to its overflow chain.
+pub type HashText = ExtendibleHash<String, TextEntry>;
+
+impl Keyed for TextEntry {
+    type Key = String;
+
+    const UNIQUE: bool = false;
+
+    fn key(&self) -> &String {
+        &self.0
+    }
+
+    fn label(&self) -> String {
+        self.1.to_string()
+    }
+}
+
+impl Encode for TextEntry {
+    type Layout = ();
+
+    const KIND: u8 = 3;
+
+    // | 8B rid | text |
+    fn nominal_size(_: &()) -> usize {
+        104
+    }
+
+    fn encode(&self, _: &(), buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(&(self.1 as u64).to_be_bytes());
+        buf.extend_from_slice(self.0.as_bytes());
+
+        Ok(())
+    }
+
+    fn decode<R: Read>(_: &(), r: &mut Reader<R>, len: usize) -> Result<Self> {
+        if len < 8 {
+            return Err(r.corrupt(format!("entrada com {len} bytes")));
+        }
+
+        let rid = bucket::read_rid(r)?;
+        let text = r.read_str(len - 8)?;
+
+        Ok((text, rid))
+    }
+}
+
+impl HashText {
+    /// Rids in `HashAlt1` of every record with this text.
+    pub fn lookup_all(&self, text: &str) -> Vec<Rid> {
+        self.search_all(&text.to_string())
+            .into_iter()
+            .map(|r| self.get(r).unwrap().1)
+            .collect()
+    }
+
+    /// Every record with this text, read from the primary index.
+    pub fn records<'a>(&self, primary: &'a HashAlt1, text: &str) -> Vec<&'a Record> {
+        self.lookup_all(text)
+            .into_iter()
+            .filter_map(|rid| primary.get(rid))
+            .collect()
+    }
+
+    /// Removes the entry pointing to one record.
+    pub fn remove_record(&mut self, text: &str, rid: Rid) -> Result<()> {
+        let entry = self
+            .search_all(&text.to_string())
+            .into_iter()
+            .find(|r| self.get(*r).unwrap().1 == rid)
+            .ok_or_else(|| Error::KeyNotFound(format!("{text:?} -> {rid}")))?;
+
+        self.remove_rid(entry)?;
+
+        Ok(())
+    }
+}
+
+impl SecondaryIndex for HashText {
+    fn insert(&mut self, rid: Rid, record: &Record) -> Result<()> {
+        ExtendibleHash::insert(self, (record.text.clone(), rid))?;
+
+        Ok(())
+    }
+
+    fn remove(&mut self, rid: Rid, record: &Record) -> Result<()> {
+        self.remove_record(&record.text, rid)
+    }
+
+    fn lookup(&self, probe: &Record) -> Vec<Rid> {
+        self.lookup_all(&probe.text)
+    }
+
+    fn len(&self) -> usize {
+        ExtendibleHash::len(self)
+    }
+
+    fn clear(&mut self) {
+        self.drain();
+    }
+
+    fn check_invariants(&self) -> std::result::Result<(), String> {
+        ExtendibleHash::check_invariants(self)
+    }
+
+    fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        ExtendibleHash::write_to(self, w)
+    }
+
+    fn read_from(&mut self, r: &mut dyn Read) -> Result<()> {
+        *self = Self::read_from(r)?;
+
+        Ok(())
+    }
+
+    fn create_store(&mut self, path: &str) -> Result<Store> {
+        Store::create(path, self)
+    }
+
+    fn open_store(&mut self, path: &str) -> Result<Store> {
+        let (store, h) = Store::open(path)?;
+        *self = h;
+
+        Ok(store)
+    }
+
+    fn flush(&mut self, store: &mut Store, sync: bool) -> Result<()> {
+        store.flush(self, sync)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::entry::Entry;
+
+    fn build(n: i32) -> (HashAlt1, HashText) {
+        let mut h1 = HashAlt1::new(1, 2).unwrap();
+        let mut ht = HashText::new(1, 2).unwrap();
+
+        for nseq in 0..n {
+            let text = format!("texto {}", nseq % 3);
+            let rid = h1
+                .insert(Record {
+                    nseq,
+                    text: text.clone(),
+                })
+                .unwrap();
+
+            ht.insert((text, rid)).unwrap();
+        }
+
+        (h1, ht)
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let (h1, ht) = build(30);
+
+        println!("{ht}");
+
+        assert_eq!(ht.check_invariants(), Ok(()));
+        assert_eq!(ht.len(), 30);
+
+        // So tres textos: as repeticoes vao para overflow em vez de dividir ate o fim
+        assert!(ht.global_depth() < 4);
+
+        let mut nseqs: Vec<i32> = ht.records(&h1, "texto 1").iter().map(|r| r.nseq).collect();
+        nseqs.sort();
+
+        assert_eq!(nseqs, (1..30).step_by(3).collect::<Vec<i32>>());
+        assert!(ht.lookup_all("texto 9").is_empty());
+    }
+
+    #[test]
+    fn test_remove_record() {
+        let (mut h1, mut ht) = build(30);
+
+        for nseq in (0..30).step_by(3) {
+            let rid = h1.search(&nseq).unwrap();
+
+            ht.remove_record("texto 0", rid).unwrap();
+            h1.remove(&nseq).unwrap();
+
+            assert_eq!(ht.check_invariants(), Ok(()));
+        }
+
+        assert!(ht.lookup_all("texto 0").is_empty());
+        assert_eq!(ht.lookup_all("texto 2").len(), 10);
+        assert!(matches!(
+            ht.remove_record("texto 0", 0),
+            Err(Error::KeyNotFound(_))
+        ));
+
+        // A entrada tira o registro dela pelo rid
+        let Entry::Occupied(e) = ht.entry("texto 1".to_string()) else {
+            unreachable!()
+        };
+        let rid = e.rid();
+        let removed = e.remove().unwrap();
+
+        assert!(ht.get(rid).is_none());
+        assert!(!ht.lookup_all("texto 1").contains(&removed.1));
+        assert_eq!(ht.lookup_all("texto 1").len(), 9);
+    }
+
+    #[test]
+    fn test_retain() {
+        let (h1, mut ht) = build(30);
+
+        // Mantem so os registros impares de cada texto repetido
+        ht.retain(|e| h1.get(e.1).unwrap().nseq % 2 == 1);
+
+        assert_eq!(ht.check_invariants(), Ok(()));
+        assert_eq!(ht.len(), 15);
+
+        for text in ["texto 0", "texto 1", "texto 2"] {
+            assert!(ht.records(&h1, text).iter().all(|r| r.nseq % 2 == 1));
+        }
+
+        assert_eq!(ht.drain().count(), 15);
+        assert!(ht.is_empty());
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let (h1, ht) = build(30);
+
+        let encoded = ht.serialize().unwrap();
+        let mut ht2 = HashText::read_from(&mut &encoded[..]).unwrap();
+
+        assert_eq!(ht2.check_invariants(), Ok(()));
+        assert_eq!(format!("{ht2}"), format!("{ht}"));
+        assert_eq!(ht2.records(&h1, "texto 2").len(), 10);
+
+        ht2.insert(("texto 2".to_string(), 99)).unwrap();
+
+        assert_eq!(ht2.lookup_all("texto 2").len(), 11);
+    }
+}
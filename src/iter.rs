@@ -0,0 +1,63 @@
+use std::{slice, vec};
+
+use crate::bucket::Bucket;
+
+/// Records of an `ExtendibleHash`, bucket by bucket. Free buckets are empty,
+/// so walking the bucket vector yields every record exactly once no matter
+/// how many directory entries point at its bucket.
+pub struct Iter<'a, V> {
+    pub(crate) buckets: slice::Iter<'a, Bucket<V>>,
+    pub(crate) data: slice::Iter<'a, V>,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        loop {
+            if let Some(v) = self.data.next() {
+                return Some(v);
+            }
+
+            self.data = self.buckets.next()?.data.iter();
+        }
+    }
+}
+
+pub struct IterMut<'a, V> {
+    pub(crate) buckets: slice::IterMut<'a, Bucket<V>>,
+    pub(crate) data: slice::IterMut<'a, V>,
+}
+
+impl<'a, V> Iterator for IterMut<'a, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        loop {
+            if let Some(v) = self.data.next() {
+                return Some(v);
+            }
+
+            self.data = self.buckets.next()?.data.iter_mut();
+        }
+    }
+}
+
+pub struct IntoIter<V> {
+    pub(crate) buckets: vec::IntoIter<Bucket<V>>,
+    pub(crate) data: vec::IntoIter<V>,
+}
+
+impl<V> Iterator for IntoIter<V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        loop {
+            if let Some(v) = self.data.next() {
+                return Some(v);
+            }
+
+            self.data = self.buckets.next()?.data.into_iter();
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    fmt::Debug,
+    hash::Hash,
+    io::{Read, Write},
+};
+
+use crate::{
+    bucket,
+    error::{Error, Result},
+    extendible_hash::{ExtendibleHash, Rid},
+    reader::Reader,
+    record::Record,
+    store::Store,
+    table::SecondaryIndex,
+    traits::{Encode, Keyed},
+};
+
+/// A key that can be stored in a `KeyIndex`.
+pub trait IndexKey: Hash + PartialEq + Clone + Debug {
+    /// Tag of the key type in the header of a saved index, 1 to 15.
+    const ID: u8 = 0;
+
+    /// Encoded size pages are planned around.
+    const SIZE: usize;
+
+    fn write(&self, buf: &mut Vec<u8>);
+
+    /// Reads a key written by `write` in `len` bytes.
+    fn read<R: Read>(r: &mut Reader<R>, len: usize) -> Result<Self>;
+}
+
+impl IndexKey for i32 {
+    const ID: u8 = 1;
+    const SIZE: usize = 4;
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_be_bytes());
+    }
+
+    fn read<R: Read>(r: &mut Reader<R>, _: usize) -> Result<Self> {
+        r.read_i32()
+    }
+}
+
+impl IndexKey for i64 {
+    const ID: u8 = 2;
+    const SIZE: usize = 8;
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_be_bytes());
+    }
+
+    fn read<R: Read>(r: &mut Reader<R>, _: usize) -> Result<Self> {
+        Ok(r.read_u64()? as i64)
+    }
+}
+
+impl IndexKey for usize {
+    const ID: u8 = 3;
+    const SIZE: usize = 8;
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&(*self as u64).to_be_bytes());
+    }
+
+    fn read<R: Read>(r: &mut Reader<R>, _: usize) -> Result<Self> {
+        Ok(r.read_u64()? as usize)
+    }
+}
+
+impl IndexKey for String {
+    const ID: u8 = 4;
+    // Mesmo tamanho nominal do campo text
+    const SIZE: usize = 96;
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn read<R: Read>(r: &mut Reader<R>, len: usize) -> Result<Self> {
+        r.read_str(len)
+    }
+}
+
+/// Slot of a `KeyIndex`: a key and the rid of a record with it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyEntry<K> {
+    pub key: K,
+    pub rid: Rid,
+}
+
+impl<K: IndexKey> Keyed for KeyEntry<K> {
+    type Key = K;
+
+    const UNIQUE: bool = false;
+
+    fn key(&self) -> &K {
+        &self.key
+    }
+
+    fn label(&self) -> String {
+        self.rid.to_string()
+    }
+}
+
+impl<K: IndexKey> Encode for KeyEntry<K> {
+    type Layout = ();
+
+    const KIND: u8 = 0x10 | K::ID;
+
+    // | 8B rid | key |
+    fn nominal_size(_: &()) -> usize {
+        8 + K::SIZE
+    }
+
+    fn encode(&self, _: &(), buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(&(self.rid as u64).to_be_bytes());
+        self.key.write(buf);
+
+        Ok(())
+    }
+
+    fn decode<R: Read>(_: &(), r: &mut Reader<R>, len: usize) -> Result<Self> {
+        if len < 8 {
+            return Err(r.corrupt(format!("entrada com {len} bytes")));
+        }
+
+        let rid = bucket::read_rid(r)?;
+        let key = K::read(r, len - 8)?;
+
+        Ok(KeyEntry { key, rid })
+    }
+}
+
+/// Non-unique secondary index on any key computed from a `Record`, e.g.
+/// `KeyIndex::new(1, 4, |r: &Record| r.text.len())`.
+pub struct KeyIndex<K: IndexKey> {
+    hash: ExtendibleHash<K, KeyEntry<K>>,
+    key: Box<dyn Fn(&Record) -> K>,
+}
+
+impl<K: IndexKey> KeyIndex<K> {
+    pub fn new<F: Fn(&Record) -> K + 'static>(
+        global_depth: u8,
+        bucket_size: u32,
+        key: F,
+    ) -> Result<Self> {
+        Ok(Self::with_hash(
+            ExtendibleHash::new(global_depth, bucket_size)?,
+            key,
+        ))
+    }
+
+    /// Uses a table set up by the caller (hash function, merge threshold...).
+    pub fn with_hash<F: Fn(&Record) -> K + 'static>(
+        hash: ExtendibleHash<K, KeyEntry<K>>,
+        key: F,
+    ) -> Self {
+        KeyIndex {
+            hash,
+            key: Box::new(key),
+        }
+    }
+
+    pub fn hash(&self) -> &ExtendibleHash<K, KeyEntry<K>> {
+        &self.hash
+    }
+
+    /// Rids of the records with this key.
+    pub fn lookup_key(&self, key: &K) -> Vec<Rid> {
+        self.hash
+            .search_all(key)
+            .into_iter()
+            .map(|r| self.hash.get(r).unwrap().rid)
+            .collect()
+    }
+}
+
+impl<K: IndexKey> SecondaryIndex for KeyIndex<K> {
+    fn insert(&mut self, rid: Rid, record: &Record) -> Result<()> {
+        self.hash.insert(KeyEntry {
+            key: (self.key)(record),
+            rid,
+        })?;
+
+        Ok(())
+    }
+
+    fn remove(&mut self, rid: Rid, record: &Record) -> Result<()> {
+        let key = (self.key)(record);
+        let entry = self
+            .hash
+            .search_all(&key)
+            .into_iter()
+            .find(|r| self.hash.get(*r).unwrap().rid == rid)
+            .ok_or_else(|| Error::KeyNotFound(format!("{key:?} -> {rid}")))?;
+
+        self.hash.remove_rid(entry)?;
+
+        Ok(())
+    }
+
+    fn lookup(&self, probe: &Record) -> Vec<Rid> {
+        self.lookup_key(&(self.key)(probe))
+    }
+
+    fn len(&self) -> usize {
+        self.hash.len()
+    }
+
+    fn clear(&mut self) {
+        self.hash.drain();
+    }
+
+    fn check_invariants(&self) -> std::result::Result<(), String> {
+        self.hash.check_invariants()
+    }
+
+    fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        self.hash.write_to(w)
+    }
+
+    fn read_from(&mut self, r: &mut dyn Read) -> Result<()> {
+        self.hash = ExtendibleHash::read_from(r)?;
+
+        Ok(())
+    }
+
+    fn create_store(&mut self, path: &str) -> Result<Store> {
+        Store::create(path, &mut self.hash)
+    }
+
+    fn open_store(&mut self, path: &str) -> Result<Store> {
+        let (store, hash) = Store::open(path)?;
+        self.hash = hash;
+
+        Ok(store)
+    }
+
+    fn flush(&mut self, store: &mut Store, sync: bool) -> Result<()> {
+        store.flush(&mut self.hash, sync)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{hash_alt1::HashAlt1, table::Table, temp_path};
+
+    fn table() -> Table {
+        let mut t = Table::new(HashAlt1::new(1, 4).unwrap());
+
+        for nseq in 0..40 {
+            t.insert(Record {
+                nseq,
+                text: "x".repeat(nseq as usize % 7 + 1),
+            })
+            .unwrap();
+        }
+
+        t
+    }
+
+    #[test]
+    fn test_register_builds_from_records() {
+        let mut t = table();
+
+        t.register(
+            "len",
+            Box::new(KeyIndex::new(1, 4, |r: &Record| r.text.len()).unwrap()),
+        )
+        .unwrap();
+        t.register(
+            "mod",
+            Box::new(KeyIndex::new(1, 4, |r: &Record| r.nseq % 10).unwrap()),
+        )
+        .unwrap();
+
+        assert_eq!(t.check_consistency(), Ok(()));
+
+        let probe = Record {
+            nseq: 0,
+            text: "xxx".to_string(),
+        };
+        let mut nseqs: Vec<i32> = t
+            .find("len", &probe)
+            .unwrap()
+            .iter()
+            .map(|r| r.nseq)
+            .collect();
+        nseqs.sort();
+
+        assert_eq!(nseqs, vec![2, 9, 16, 23, 30, 37]);
+        assert_eq!(t.find("mod", &probe).unwrap().len(), 4);
+
+        t.update(Record {
+            nseq: 2,
+            text: "y".to_string(),
+        })
+        .unwrap();
+        t.remove(9).unwrap();
+
+        assert_eq!(t.find("len", &probe).unwrap().len(), 4);
+        assert_eq!(t.check_consistency(), Ok(()));
+    }
+
+    #[test]
+    fn test_lookup_key() {
+        let mut index = KeyIndex::new(1, 2, |r: &Record| {
+            r.text.chars().take(2).collect::<String>()
+        })
+        .unwrap();
+
+        for (rid, text) in ["abc", "abd", "xyz", "ab"].iter().enumerate() {
+            index
+                .insert(
+                    rid,
+                    &Record {
+                        nseq: rid as i32,
+                        text: text.to_string(),
+                    },
+                )
+                .unwrap();
+        }
+
+        assert_eq!(index.lookup_key(&"ab".to_string()).len(), 3);
+        assert_eq!(index.lookup_key(&"xy".to_string()), vec![2]);
+        assert!(index.lookup_key(&"zz".to_string()).is_empty());
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut t = table();
+
+        t.register(
+            "mod",
+            Box::new(KeyIndex::new(1, 4, |r: &Record| r.nseq % 10).unwrap()),
+        )
+        .unwrap();
+
+        let prefix = temp_path("key_index_test");
+        t.save(&prefix).unwrap();
+
+        assert_eq!(
+            Table::saved_indexes(&prefix).unwrap(),
+            vec!["mod".to_string()]
+        );
+
+        // Sem registrar o indice salvo a carga falha
+        let mut t2 = Table::new(HashAlt1::new(1, 4).unwrap());
+
+        assert!(matches!(t2.load(&prefix), Err(Error::IndexNotFound(_))));
+
+        t2.register(
+            "mod",
+            Box::new(KeyIndex::new(1, 4, |r: &Record| r.nseq % 10).unwrap()),
+        )
+        .unwrap();
+        t2.load(&prefix).unwrap();
+
+        assert_eq!(t2.check_consistency(), Ok(()));
+        assert_eq!(t2.index("mod").unwrap().len(), 40);
+    }
+}
@@ -0,0 +1,51 @@
+pub mod bucket;
+pub mod buffer_pool;
+pub mod crc32;
+pub mod disk_hash;
+pub mod entry;
+pub mod error;
+pub mod extendible_hash;
+pub mod format;
+pub mod hash_alt1;
+pub mod hash_alt2;
+pub mod hash_function;
+pub mod hash_text;
+pub mod iter;
+pub mod key_index;
+pub mod reader;
+pub mod record;
+pub mod row;
+pub mod schema;
+pub mod store;
+pub mod table;
+pub mod traits;
+pub mod wal;
+
+pub use bucket::Bucket;
+pub use buffer_pool::{BufferPool, PageFile, Policy};
+pub use disk_hash::DiskHash;
+pub use entry::Entry;
+pub use error::{Error, Result};
+pub use extendible_hash::{ExtendibleHash, Rid};
+pub use hash_alt1::HashAlt1;
+pub use hash_alt2::{HashAlt2, IndexEntry};
+pub use hash_function::HashFunction;
+pub use hash_text::{HashText, TextEntry};
+pub use key_index::{IndexKey, KeyIndex};
+pub use record::Record;
+pub use row::{HashRows, Row, Value};
+pub use schema::{Field, FieldType, Schema};
+pub use store::Store;
+pub use table::{SecondaryIndex, Table};
+pub use traits::{Encode, Keyed};
+pub use wal::{LogRecord, Lsn, SyncPolicy, Wal};
+
+/// Path in the temporary directory for a file written by a test, with the
+/// process id so parallel runs do not share it.
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("extendible_hash_{}_{name}", std::process::id()))
+        .to_string_lossy()
+        .into_owned()
+}
@@ -0,0 +1,125 @@
+use std::io::{ErrorKind, Read};
+
+use crate::{
+    crc32::Crc32,
+    error::{Error, Result},
+};
+
+/// Wraps a `Read`, keeping track of the offset so decoding errors can say
+/// where the file is broken, and of the CRC-32 of what was read since
+/// `begin_checksum`.
+pub struct Reader<R> {
+    inner: R,
+    offset: u64,
+    crc: Crc32,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self::at(inner, 0)
+    }
+
+    /// Reads a part of a file that starts at byte `offset`.
+    pub fn at(inner: R, offset: u64) -> Self {
+        Reader {
+            inner,
+            offset,
+            crc: Crc32::new(),
+        }
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn begin_checksum(&mut self) {
+        self.crc = Crc32::new();
+    }
+
+    /// CRC-32 of the bytes read since `begin_checksum`.
+    pub fn checksum(&self) -> u32 {
+        self.crc.finish()
+    }
+
+    /// Reads a 4 byte CRC-32 and compares it with `checksum`, failing with
+    /// `Checksum` at byte `start` for `part`.
+    pub fn verify_checksum(&mut self, start: u64, part: impl Into<String>) -> Result<()> {
+        let crc = self.checksum();
+
+        if self.read_u32()? != crc {
+            return Err(Error::Checksum {
+                offset: start,
+                part: part.into(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// A `Corrupt` error at the current offset.
+    pub fn corrupt(&self, reason: impl Into<String>) -> Error {
+        Error::Corrupt {
+            offset: self.offset,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        match self.inner.read_exact(buf) {
+            Ok(()) => {
+                self.offset += buf.len() as u64;
+                self.crc.update(buf);
+                Ok(())
+            }
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                Err(self.corrupt("fim inesperado do arquivo"))
+            }
+            Err(e) => Err(Error::Io(e)),
+        }
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8> {
+        let mut buffer = [0; 1];
+        self.read_exact(&mut buffer)?;
+        Ok(buffer[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16> {
+        let mut buffer = [0; 2];
+        self.read_exact(&mut buffer)?;
+        Ok(u16::from_be_bytes(buffer))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32> {
+        let mut buffer = [0; 4];
+        self.read_exact(&mut buffer)?;
+        Ok(u32::from_be_bytes(buffer))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64> {
+        let mut buffer = [0; 8];
+        self.read_exact(&mut buffer)?;
+        Ok(u64::from_be_bytes(buffer))
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32> {
+        let mut buffer = [0; 4];
+        self.read_exact(&mut buffer)?;
+        Ok(i32::from_be_bytes(buffer))
+    }
+
+    /// Reads a `len` byte text field padded with `\0`.
+    pub fn read_str(&mut self, len: usize) -> Result<String> {
+        let start = self.offset;
+        let mut buffer = vec![0; len];
+        self.read_exact(&mut buffer)?;
+
+        match String::from_utf8(buffer) {
+            Ok(s) => Ok(s.trim_end_matches('\0').to_string()),
+            Err(_) => Err(Error::Corrupt {
+                offset: start,
+                reason: "texto com UTF-8 invalido".to_string(),
+            }),
+        }
+    }
+}
@@ -1,5 +1,64 @@
+use std::io::Read;
+
+use crate::{
+    error::Result,
+    reader::Reader,
+    traits::{Encode, Keyed},
+};
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Record {
     pub nseq: i32,
     pub text: String,
 }
+
+impl Keyed for Record {
+    type Key = i32;
+
+    fn key(&self) -> &i32 {
+        &self.nseq
+    }
+
+    fn label(&self) -> String {
+        self.nseq.to_string()
+    }
+}
+
+impl Encode for Record {
+    type Layout = ();
+
+    const KIND: u8 = 1;
+
+    // | 4B nseq | text |
+    fn nominal_size(_: &()) -> usize {
+        100
+    }
+
+    fn encode(&self, _: &(), buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(&self.nseq.to_be_bytes());
+        buf.extend_from_slice(self.text.as_bytes());
+
+        Ok(())
+    }
+
+    fn decode<R: Read>(_: &(), r: &mut Reader<R>, len: usize) -> Result<Self> {
+        if len < 4 {
+            return Err(r.corrupt(format!("registro com {len} bytes")));
+        }
+
+        let nseq = r.read_i32()?;
+        let text = r.read_str(len - 4)?;
+
+        Ok(Record { nseq, text })
+    }
+
+    // | 4B nseq | 96B text |, sem texto o slot esta vazio
+    const LEGACY_SIZE: usize = 100;
+
+    fn decode_legacy<R: Read>(r: &mut Reader<R>) -> Result<Option<Self>> {
+        let nseq = r.read_i32()?;
+        let text = r.read_str(96)?.trim_start_matches('\0').to_string();
+
+        Ok((!text.is_empty()).then_some(Record { nseq, text }))
+    }
+}
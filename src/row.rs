@@ -0,0 +1,337 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    io::Read,
+};
+
+use crate::{
+    error::{Error, Result},
+    extendible_hash::ExtendibleHash,
+    reader::Reader,
+    record::Record,
+    schema::{FieldType, Schema},
+    traits::{Encode, Keyed},
+};
+
+/// Table of `Row`s keyed by their first field. Its schema is set with
+/// `ExtendibleHash::with_layout` and saved in the file header.
+pub type HashRows = ExtendibleHash<Value, Row>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    F64(f64),
+    Bool(bool),
+    /// `FixedStr` or `VarStr` field.
+    Str(String),
+    Bytes(Vec<u8>),
+}
+
+// So o conteudo entra no hash: chaves inteiras passam intactas por LowBits
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Value::I32(v) => v.hash(state),
+            Value::I64(v) => v.hash(state),
+            Value::F64(v) => v.to_bits().hash(state),
+            Value::Bool(v) => v.hash(state),
+            Value::Str(v) => v.hash(state),
+            Value::Bytes(v) => v.hash(state),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::I32(v) => write!(f, "{v}"),
+            Value::I64(v) => write!(f, "{v}"),
+            Value::F64(v) => write!(f, "{v}"),
+            Value::Bool(v) => write!(f, "{v}"),
+            Value::Str(v) => write!(f, "{v}"),
+            Value::Bytes(v) => write!(f, "{} bytes", v.len()),
+        }
+    }
+}
+
+/// Record with the fields of a `Schema`, in the same order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub values: Vec<Value>,
+}
+
+impl Row {
+    pub fn new(values: Vec<Value>) -> Self {
+        Row { values }
+    }
+
+    /// Value of the field called `name` in `schema`.
+    pub fn get(&self, schema: &Schema, name: &str) -> Option<&Value> {
+        self.values.get(schema.index_of(name)?)
+    }
+}
+
+/// A row of the default schema.
+impl From<Record> for Row {
+    fn from(r: Record) -> Self {
+        Row::new(vec![Value::I32(r.nseq), Value::Str(r.text)])
+    }
+}
+
+impl Keyed for Row {
+    type Key = Value;
+
+    fn key(&self) -> &Value {
+        &self.values[0]
+    }
+
+    fn label(&self) -> String {
+        self.key().to_string()
+    }
+}
+
+/// Appends a string or bytes field with at most `max` bytes.
+fn write_var(bytes: &[u8], max: u16, buf: &mut Vec<u8>) -> Result<()> {
+    if bytes.len() > max as usize {
+        return Err(Error::RecordTooLarge {
+            size: bytes.len(),
+            max: max as usize,
+        });
+    }
+
+    buf.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    buf.extend_from_slice(bytes);
+
+    Ok(())
+}
+
+fn read_var<R: Read>(r: &mut Reader<R>, max: u16) -> Result<Vec<u8>> {
+    let len = r.read_u16()?;
+
+    if len > max {
+        return Err(r.corrupt(format!("campo com {len} bytes, o maximo e {max}")));
+    }
+
+    let mut bytes = vec![0; len as usize];
+    r.read_exact(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+impl Encode for Row {
+    type Layout = Schema;
+
+    const KIND: u8 = 4;
+
+    // | campo1 | campo2 |...| (campos variaveis so com os bytes usados)
+    fn nominal_size(schema: &Schema) -> usize {
+        schema.record_size()
+    }
+
+    fn encode(&self, schema: &Schema, buf: &mut Vec<u8>) -> Result<()> {
+        let fields = schema.fields();
+
+        if self.values.len() != fields.len() {
+            return Err(Error::Schema(format!(
+                "registro com {} campos, o esquema tem {}",
+                self.values.len(),
+                fields.len()
+            )));
+        }
+
+        for (v, f) in self.values.iter().zip(fields) {
+            match (v, f.ty) {
+                (Value::I32(v), FieldType::I32) => buf.extend_from_slice(&v.to_be_bytes()),
+                (Value::I64(v), FieldType::I64) => buf.extend_from_slice(&v.to_be_bytes()),
+                (Value::F64(v), FieldType::F64) => buf.extend_from_slice(&v.to_be_bytes()),
+                (Value::Bool(v), FieldType::Bool) => buf.push(*v as u8),
+                (Value::Str(v), FieldType::FixedStr(n)) => {
+                    if v.len() > n as usize {
+                        return Err(Error::RecordTooLarge {
+                            size: v.len(),
+                            max: n as usize,
+                        });
+                    }
+
+                    let start = buf.len();
+                    buf.extend_from_slice(v.as_bytes());
+                    buf.resize(start + n as usize, 0);
+                }
+                (Value::Str(v), FieldType::VarStr(n)) => write_var(v.as_bytes(), n, buf)?,
+                (Value::Bytes(v), FieldType::Bytes(n)) => write_var(v, n, buf)?,
+                _ => {
+                    return Err(Error::Schema(format!(
+                        "campo {} deveria ser {:?}",
+                        f.name, f.ty
+                    )))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn decode<R: Read>(schema: &Schema, r: &mut Reader<R>, len: usize) -> Result<Self> {
+        let start = r.offset();
+        let mut values = Vec::with_capacity(schema.fields().len());
+
+        for f in schema.fields() {
+            let v = match f.ty {
+                FieldType::I32 => Value::I32(r.read_i32()?),
+                FieldType::I64 => Value::I64(r.read_u64()? as i64),
+                FieldType::F64 => Value::F64(f64::from_bits(r.read_u64()?)),
+                FieldType::Bool => Value::Bool(r.read_u8()? != 0),
+                FieldType::FixedStr(n) => Value::Str(r.read_str(n as usize)?),
+                FieldType::VarStr(n) => {
+                    let offset = r.offset();
+
+                    Value::Str(
+                        String::from_utf8(read_var(r, n)?).map_err(|_| Error::Corrupt {
+                            offset,
+                            reason: "texto com UTF-8 invalido".to_string(),
+                        })?,
+                    )
+                }
+                FieldType::Bytes(n) => Value::Bytes(read_var(r, n)?),
+            };
+
+            values.push(v);
+        }
+
+        if r.offset() - start != len as u64 {
+            return Err(Error::Corrupt {
+                offset: start,
+                reason: format!("registro com {len} bytes nao bate com o esquema"),
+            });
+        }
+
+        Ok(Row { values })
+    }
+
+    fn write_layout(schema: &Schema, buf: &mut Vec<u8>) {
+        schema.write(buf);
+    }
+
+    fn read_layout<R: Read>(r: &mut Reader<R>) -> Result<Schema> {
+        Schema::read(r)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{hash_function::LowBits, schema::Field};
+
+    fn schema() -> Schema {
+        Schema::new(vec![
+            Field::new("id", FieldType::I64),
+            Field::new("preco", FieldType::F64),
+            Field::new("ativo", FieldType::Bool),
+            Field::new("codigo", FieldType::FixedStr(4)),
+            Field::new("nome", FieldType::VarStr(20)),
+            Field::new("dados", FieldType::Bytes(8)),
+        ])
+        .unwrap()
+    }
+
+    fn row(id: i64) -> Row {
+        Row::new(vec![
+            Value::I64(id),
+            Value::F64(id as f64 / 2.0),
+            Value::Bool(id % 2 == 0),
+            Value::Str("AB".to_string()),
+            Value::Str(format!("produto {id}")),
+            Value::Bytes(vec![id as u8; (id % 8) as usize]),
+        ])
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let s = schema();
+        let mut r = row(1);
+
+        r.values[2] = Value::I32(1);
+        assert!(matches!(r.validate(&s), Err(Error::Schema(_))));
+
+        r.values.pop();
+        assert!(matches!(r.validate(&s), Err(Error::Schema(_))));
+
+        let mut r = row(1);
+
+        r.values[4] = Value::Str("x".repeat(21));
+        assert!(matches!(
+            r.validate(&s),
+            Err(Error::RecordTooLarge { size: 21, max: 20 })
+        ));
+    }
+
+    #[test]
+    fn test_schema_in_header() {
+        let mut h = HashRows::with_layout(1, 2, Box::new(LowBits), schema()).unwrap();
+
+        for id in 0..20 {
+            h.insert(row(id)).unwrap();
+        }
+
+        assert!(h
+            .insert(Row::from(Record {
+                nseq: 1,
+                text: "x".to_string()
+            }))
+            .is_err());
+
+        let encoded = h.serialize().unwrap();
+        let buckets = h.bucket_count();
+        let mut header = Vec::new();
+        schema().write(&mut header);
+
+        assert_eq!(
+            encoded.len(),
+            6 + 1
+                + 1
+                + 1
+                + 4
+                + 8
+                + header.len()
+                + 4
+                + 4
+                + 4
+                + 4
+                + buckets * (21 + 2 * (17 + 8 + 8 + 1 + 4 + 22 + 10) + 4)
+                + 8
+                + 4 * h.directory().len()
+                + 4
+        );
+
+        let h2 = HashRows::read_from(&mut &encoded[..]).unwrap();
+
+        assert_eq!(h2.layout(), &schema());
+        assert_eq!(h2.check_invariants(), Ok(()));
+
+        for id in 0..20 {
+            let rid = h2.search(&Value::I64(id)).unwrap();
+
+            assert_eq!(h2.get(rid), Some(&row(id)));
+        }
+    }
+
+    #[test]
+    fn test_default_schema() {
+        let mut h: HashRows = HashRows::new(1, 4).unwrap();
+
+        h.insert(Row::from(Record {
+            nseq: 7,
+            text: "sete".to_string(),
+        }))
+        .unwrap();
+
+        let rid = h.search(&Value::I32(7)).unwrap();
+        let r = h.get(rid).unwrap();
+
+        assert_eq!(
+            r.get(h.layout(), "text"),
+            Some(&Value::Str("sete".to_string()))
+        );
+    }
+}
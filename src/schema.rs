@@ -0,0 +1,246 @@
+use std::io::Read;
+
+use crate::{
+    error::{Error, Result},
+    reader::Reader,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldType {
+    I32,
+    I64,
+    F64,
+    Bool,
+    /// Text padded with `\0` to exactly this many bytes.
+    FixedStr(u16),
+    /// Text of at most this many bytes, stored with its length.
+    VarStr(u16),
+    /// At most this many bytes, stored with their length.
+    Bytes(u16),
+}
+
+impl FieldType {
+    /// Most bytes the field takes in an encoded row.
+    pub fn size(&self) -> usize {
+        match self {
+            FieldType::I32 => 4,
+            FieldType::I64 | FieldType::F64 => 8,
+            FieldType::Bool => 1,
+            FieldType::FixedStr(n) => *n as usize,
+            FieldType::VarStr(n) | FieldType::Bytes(n) => 2 + *n as usize,
+        }
+    }
+
+    fn id(&self) -> (u8, u16) {
+        match self {
+            FieldType::I32 => (0, 0),
+            FieldType::I64 => (1, 0),
+            FieldType::F64 => (2, 0),
+            FieldType::Bool => (3, 0),
+            FieldType::FixedStr(n) => (4, *n),
+            FieldType::VarStr(n) => (5, *n),
+            FieldType::Bytes(n) => (6, *n),
+        }
+    }
+
+    fn from_id(id: u8, len: u16) -> Option<Self> {
+        match id {
+            0 => Some(FieldType::I32),
+            1 => Some(FieldType::I64),
+            2 => Some(FieldType::F64),
+            3 => Some(FieldType::Bool),
+            4 => Some(FieldType::FixedStr(len)),
+            5 => Some(FieldType::VarStr(len)),
+            6 => Some(FieldType::Bytes(len)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub ty: FieldType,
+}
+
+impl Field {
+    pub fn new(name: &str, ty: FieldType) -> Self {
+        Field {
+            name: name.to_string(),
+            ty,
+        }
+    }
+}
+
+/// Named, typed fields of a `Row`. The first field is the key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schema {
+    fields: Vec<Field>,
+}
+
+impl Schema {
+    /// Fails if there are no fields or over 255 of them, a name repeats or is
+    /// over 255 bytes, or the key is a `F64` (NaN is not equal to itself).
+    pub fn new(fields: Vec<Field>) -> Result<Self> {
+        // O numero de campos e salvo em 1 byte
+        if fields.len() > u8::MAX as usize {
+            return Err(Error::Schema(format!(
+                "{} campos, o maximo e {}",
+                fields.len(),
+                u8::MAX
+            )));
+        }
+
+        match fields.first() {
+            None => return Err(Error::Schema("nenhum campo".to_string())),
+            Some(f) if f.ty == FieldType::F64 => {
+                return Err(Error::Schema(format!(
+                    "a chave {} nao pode ser f64",
+                    f.name
+                )))
+            }
+            _ => {}
+        }
+
+        for (i, f) in fields.iter().enumerate() {
+            if f.name.is_empty() || f.name.len() > u8::MAX as usize {
+                return Err(Error::Schema(format!(
+                    "nome de campo {:?} invalido",
+                    f.name
+                )));
+            }
+
+            if fields[..i].iter().any(|g| g.name == f.name) {
+                return Err(Error::Schema(format!("campo {} repetido", f.name)));
+            }
+        }
+
+        Ok(Schema { fields })
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|f| f.name == name)
+    }
+
+    /// Size of the largest row: variable fields at their maximum.
+    pub fn record_size(&self) -> usize {
+        self.fields.iter().map(|f| f.ty.size()).sum::<usize>()
+    }
+
+    pub(crate) fn write(&self, buf: &mut Vec<u8>) {
+        // | 1B n | 1B tipo1 | 2B tam1 | 1B len nome1 | nome1 |...|
+
+        buf.push(self.fields.len() as u8);
+
+        for f in &self.fields {
+            let (id, len) = f.ty.id();
+
+            buf.push(id);
+            buf.extend_from_slice(&len.to_be_bytes());
+            buf.push(f.name.len() as u8);
+            buf.extend_from_slice(f.name.as_bytes());
+        }
+    }
+
+    pub(crate) fn read<R: Read>(r: &mut Reader<R>) -> Result<Self> {
+        let start = r.offset();
+        let n = r.read_u8()?;
+        let mut fields = Vec::with_capacity(n as usize);
+
+        for _ in 0..n {
+            let offset = r.offset();
+            let id = r.read_u8()?;
+            let len = r.read_u16()?;
+            let ty = FieldType::from_id(id, len).ok_or_else(|| Error::Corrupt {
+                offset,
+                reason: format!("tipo de campo {id} desconhecido"),
+            })?;
+
+            let name_len = r.read_u8()? as usize;
+            let name = r.read_str(name_len)?;
+
+            fields.push(Field { name, ty });
+        }
+
+        Schema::new(fields).map_err(|e| Error::Corrupt {
+            offset: start,
+            reason: e.to_string(),
+        })
+    }
+}
+
+/// The fields of `Record`: `nseq` and a 96 byte `text`.
+impl Default for Schema {
+    fn default() -> Self {
+        Schema {
+            fields: vec![
+                Field::new("nseq", FieldType::I32),
+                Field::new("text", FieldType::FixedStr(96)),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert!(Schema::new(vec![]).is_err());
+        assert!(Schema::new(vec![Field::new("x", FieldType::F64)]).is_err());
+        assert!(Schema::new(vec![
+            Field::new("id", FieldType::I64),
+            Field::new("id", FieldType::Bool),
+        ])
+        .is_err());
+
+        let many = |n: usize| {
+            (0..n)
+                .map(|i| Field::new(&format!("c{i}"), FieldType::Bool))
+                .collect()
+        };
+
+        assert!(Schema::new(many(255)).is_ok());
+        assert!(matches!(Schema::new(many(256)), Err(Error::Schema(_))));
+
+        let s = Schema::new(vec![
+            Field::new("id", FieldType::I64),
+            Field::new("preco", FieldType::F64),
+            Field::new("nome", FieldType::VarStr(20)),
+        ])
+        .unwrap();
+
+        assert_eq!(s.index_of("nome"), Some(2));
+        assert_eq!(s.record_size(), 8 + 8 + 2 + 20);
+        assert_eq!(Schema::default().record_size(), 100);
+    }
+
+    #[test]
+    fn test_write_read() {
+        let s = Schema::new(vec![
+            Field::new("id", FieldType::I32),
+            Field::new("ativo", FieldType::Bool),
+            Field::new("codigo", FieldType::FixedStr(8)),
+            Field::new("foto", FieldType::Bytes(300)),
+        ])
+        .unwrap();
+
+        let mut buf = Vec::new();
+        s.write(&mut buf);
+
+        assert_eq!(Schema::read(&mut Reader::new(&buf[..])).unwrap(), s);
+
+        // Tipo desconhecido
+        buf[1] = 9;
+
+        assert!(matches!(
+            Schema::read(&mut Reader::new(&buf[..])),
+            Err(Error::Corrupt { offset: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,1017 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    ffi::OsString,
+    fmt::Debug,
+    fs::{File, OpenOptions},
+    hash::Hash,
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bucket::{self, Bucket, SpillPages},
+    crc32::crc32,
+    error::{Error, Result},
+    extendible_hash::{ExtendibleHash, Rid, MAX_GLOBAL_DEPTH},
+    format, hash_function,
+    reader::Reader,
+    traits::{Encode, Keyed},
+};
+
+/// Global depth, max global depth, next rid and bucket count of a table.
+type Shape = (u8, u8, Rid, u32);
+
+/// Directory ranges of an entry: first index and the entries from it.
+type Ranges = Vec<(usize, Vec<usize>)>;
+
+/// Slot of a bucket no entry gave a page yet.
+const NONE: u32 = u32::MAX;
+
+/// An `ExtendibleHash` kept on disk so that a flush writes only the buckets
+/// and directory entries changed since the last one.
+///
+/// `{path}` has a header and then slots of one page each, followed by its
+/// CRC, with the bucket pages and the overflow pages of their large
+/// records. `{path}.delta` gets one entry per flush with the shape of the
+/// table, the directory ranges that changed and the slot of each bucket
+/// written:
+///
+/// `| 4B len | 1B gd | 1B max gd | 8B next rid | 4B #baldes | 4B #faixas |`
+/// `| 4B inicio | 4B n | n * 4B d |...| 4B #paginas | 4B balde | 4B slot |...| 4B crc |`
+///
+/// A changed page is written once, to a free slot, and not over the one
+/// the last entry points to: a page torn by a crash in the middle of a
+/// flush could not be rebuilt from the WAL, which only has the changes
+/// since the last sync. So such a crash leaves at most a torn entry at the
+/// end, which `open` drops, and the pages of the entry before it. Slots an
+/// entry stops pointing to are reused after the next sync. The delta is
+/// compacted once what was appended to it outgrows an entry with every
+/// bucket.
+pub struct Store {
+    path: PathBuf,
+    file: File,
+    delta: File,
+    page_size: usize,
+    /// Offset of slot 0, after the header.
+    data_start: u64,
+    delta_len: u64,
+    /// Length of the delta after the last compaction.
+    base_len: u64,
+    /// An append to the delta failed and may have left part of an entry.
+    torn: bool,
+    /// Shape in the last entry.
+    shape: Shape,
+    /// Slot of the page of each bucket.
+    location: Vec<u32>,
+    /// Slots of the overflow pages of the buckets that have any.
+    spilled: BTreeMap<usize, Vec<u32>>,
+    slots: Slots,
+    /// Slots the last entries stopped pointing to, free after a sync.
+    released: Vec<u32>,
+}
+
+impl Store {
+    /// Writes `table` whole to a new store at `path`, replacing the one
+    /// there. The delta is written last, so a store without one did not get
+    /// to the end.
+    pub fn create<P, K, V>(path: P, table: &mut ExtendibleHash<K, V>) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        K: Hash + PartialEq + Clone + Debug,
+        V: Keyed<Key = K> + Encode,
+    {
+        let path = path.as_ref().to_path_buf();
+
+        if let Err(e) = std::fs::remove_file(delta_path(&path)) {
+            if e.kind() != ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+
+        check_shape(table)?;
+        table.take_changes();
+
+        // | 4B magic | 1B versao | 1B tipo | 1B hash | 4B bucket size | layout |
+        // | 4B page size | 4B crc |
+        let mut header = Vec::new();
+        format::write_header(format::STORE_MAGIC, V::KIND, &mut header);
+        header.push(table.hash_function().id());
+        header.extend_from_slice(&table.buckets[0].size.to_be_bytes());
+        V::write_layout(table.layout(), &mut header);
+        header.extend_from_slice(&(table.page_size() as u32).to_be_bytes());
+        header.extend_from_slice(&crc32(&header).to_be_bytes());
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+
+        let mut store = Store {
+            path,
+            delta: file.try_clone()?,
+            file,
+            page_size: table.page_size(),
+            data_start: header.len() as u64,
+            delta_len: 0,
+            base_len: 0,
+            torn: false,
+            shape: shape(table),
+            location: Vec::new(),
+            spilled: BTreeMap::new(),
+            slots: Slots::default(),
+            released: Vec::new(),
+        };
+
+        store.file.write_all(&header)?;
+
+        let written = store.write_buckets(table, 0..table.buckets.len())?;
+        store.file.sync_all()?;
+        store.commit(written, table.buckets.len());
+
+        let pages: Vec<(usize, u32)> = store.location.iter().copied().enumerate().collect();
+        let directory: Vec<usize> = (0..table.directory().len()).collect();
+
+        store.replace_delta(&encode_entry(table, &directory, &pages))?;
+
+        Ok(store)
+    }
+
+    /// Opens the store at `path`, returning it with the table it holds. The
+    /// hash function must be one of the built-in ones.
+    pub fn open<P, K, V>(path: P) -> Result<(Self, ExtendibleHash<K, V>)>
+    where
+        P: AsRef<Path>,
+        K: Hash + PartialEq + Clone + Debug,
+        V: Keyed<Key = K> + Encode,
+    {
+        let path = path.as_ref().to_path_buf();
+
+        let mut file = OpenOptions::new().read(true).write(true).open(&path)?;
+        let mut r = Reader::new(&mut file);
+
+        r.begin_checksum();
+
+        // Numero magico, versao e tipo (6B)
+        format::read_header(&mut r, format::STORE_MAGIC, V::KIND)?;
+
+        // Hash function (1B)
+        let id = r.read_u8()?;
+        let hash_function = hash_function::from_id(id)
+            .ok_or_else(|| r.corrupt(format!("funcao de hash {id} desconhecida")))?;
+
+        // Bucket size (4B), layout e page size (4B)
+        let bucket_size = r.read_u32()?;
+        let layout = V::read_layout(&mut r)?;
+        let page_size = r.read_u32()? as usize;
+
+        if page_size < bucket::MIN_PAGE_SIZE {
+            return Err(r.corrupt(format!("pagina de {page_size} bytes")));
+        }
+
+        // CRC do cabecalho (4B)
+        r.verify_checksum(0, "cabecalho")?;
+
+        let data_start = r.offset();
+
+        let mut delta = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(delta_path(&path))?;
+
+        let mut bytes = Vec::new();
+        delta.read_to_end(&mut bytes)?;
+
+        let mut store = Store {
+            path,
+            file,
+            delta,
+            page_size,
+            data_start,
+            delta_len: 0,
+            base_len: 0,
+            torn: false,
+            shape: (0, 0, 0, 0),
+            location: Vec::new(),
+            spilled: BTreeMap::new(),
+            slots: Slots::default(),
+            released: Vec::new(),
+        };
+
+        // Entradas do delta, ate a primeira incompleta ou com CRC errado
+        let (shape, directory, buckets) = store.read_slots(&bytes, &layout)?;
+
+        // Descarta o resto para as proximas entradas continuarem da ultima
+        // valida
+        store.delta.set_len(store.delta_len)?;
+        store.shape = shape;
+
+        let (global_depth, max_global_depth, next_rid, m) = shape;
+        let mut rids = HashSet::new();
+
+        for (b, slot) in buckets.iter().zip(&store.location) {
+            let offset = store.offset(*slot);
+
+            if b.size() != bucket_size {
+                return Err(Error::Corrupt {
+                    offset,
+                    reason: format!(
+                        "balde {} de tamanho {}, o cabecalho diz {bucket_size}",
+                        b.label(),
+                        b.size()
+                    ),
+                });
+            }
+
+            if b.overflow.is_some_and(|o| o >= m as usize) {
+                return Err(Error::Corrupt {
+                    offset,
+                    reason: format!("overflow do balde {} fora do arquivo", b.label()),
+                });
+            }
+
+            for rid in &b.ids {
+                if *rid >= next_rid || !rids.insert(*rid) {
+                    return Err(Error::Corrupt {
+                        offset,
+                        reason: format!("rid {rid} invalido no balde {}", b.label()),
+                    });
+                }
+            }
+        }
+
+        if let Some(d) = directory.iter().find(|d| **d >= m as usize) {
+            return Err(Error::Corrupt {
+                offset: 0,
+                reason: format!("diretorio aponta para o balde {d}"),
+            });
+        }
+
+        let mut table = ExtendibleHash::with_page_size(0, page_size, hash_function, layout)?;
+        table.restore(global_depth, max_global_depth, next_rid, buckets, directory);
+
+        Ok((store, table))
+    }
+
+    /// Replays the entries of the delta in `bytes` and reads the page each
+    /// bucket ends up in, with its overflow pages. Every other slot is free.
+    fn read_slots<V: Encode>(
+        &mut self,
+        bytes: &[u8],
+        layout: &V::Layout,
+    ) -> Result<(Shape, Vec<usize>, Vec<Bucket<V>>)> {
+        let mut shape = None;
+        let mut directory = Vec::new();
+        let mut offset = 0;
+
+        while let Some((entry, len)) = decode_entry(&bytes[offset..]) {
+            replay(&entry, offset, &mut directory)?;
+
+            for (b, slot) in entry.pages {
+                if b >= self.location.len() {
+                    self.location.resize(b + 1, NONE);
+                }
+
+                self.location[b] = slot;
+            }
+
+            shape = Some(entry.shape);
+            offset += len;
+
+            // A primeira entrada e a da criacao ou da ultima compactacao
+            if self.base_len == 0 {
+                self.base_len = offset as u64;
+            }
+        }
+
+        let Some(shape) = shape else {
+            return Err(Error::Corrupt {
+                offset: 0,
+                reason: "delta sem entradas".to_string(),
+            });
+        };
+
+        let m = shape.3 as usize;
+        let len = self.file.metadata()?.len().saturating_sub(self.data_start);
+
+        self.delta_len = offset as u64;
+        self.location.resize(m, NONE);
+        self.slots.len = (len / (self.page_size as u64 + 4)) as u32;
+
+        let mut used = HashSet::new();
+        let mut buckets = Vec::with_capacity(m);
+
+        for b in 0..m {
+            let slot = self.location[b];
+
+            if slot >= self.slots.len {
+                return Err(Error::Corrupt {
+                    offset: 0,
+                    reason: format!("balde {} sem pagina", bucket::label(b as u32)),
+                });
+            }
+
+            let offset = self.offset(slot);
+            let page_size = self.page_size;
+            let page = self.read_page(slot)?;
+            let mut spill = SlotReader {
+                store: self,
+                read: Vec::new(),
+            };
+
+            let bucket = Bucket::deserialize(
+                b as u32,
+                layout,
+                page_size,
+                &mut spill,
+                &mut Reader::at(&page[..], offset),
+            )?;
+
+            let read = spill.read;
+
+            for s in std::iter::once(slot).chain(read.iter().copied()) {
+                if !used.insert(s) {
+                    return Err(Error::Corrupt {
+                        offset: self.offset(s),
+                        reason: format!("pagina do balde {} usada duas vezes", bucket.label()),
+                    });
+                }
+            }
+
+            if !read.is_empty() {
+                self.spilled.insert(b, read);
+            }
+
+            buckets.push(bucket);
+        }
+
+        self.slots.free = (0..self.slots.len).filter(|s| !used.contains(s)).collect();
+
+        Ok((shape, directory, buckets))
+    }
+
+    /// Writes the buckets and directory entries of `table` changed since
+    /// the last flush, forcing them to the disk if `sync`. `table` must be
+    /// the one the store was created or opened with.
+    pub fn flush<K, V>(&mut self, table: &mut ExtendibleHash<K, V>, sync: bool) -> Result<()>
+    where
+        K: Hash + PartialEq + Clone + Debug,
+        V: Keyed<Key = K> + Encode,
+    {
+        let changes = table.take_changes();
+
+        if changes.buckets.is_empty() && changes.directory.is_empty() && shape(table) == self.shape
+        {
+            return if sync { self.sync() } else { Ok(()) };
+        }
+
+        // Os baldes novos tambem, mesmo que a tabela nao os tenha marcado
+        let mut buckets: BTreeSet<usize> = changes
+            .buckets
+            .range(..table.buckets.len())
+            .copied()
+            .collect();
+        buckets.extend(self.location.len()..table.buckets.len());
+
+        let directory: Vec<usize> = changes
+            .directory
+            .range(..table.directory().len())
+            .copied()
+            .collect();
+
+        if let Err(e) = self.write_changes(table, &buckets, &directory, sync) {
+            // Ficam para o proximo flush
+            table.changes.buckets.extend(changes.buckets);
+            table.changes.directory.extend(changes.directory);
+
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Forces what was flushed to the disk.
+    pub fn sync(&mut self) -> Result<()> {
+        self.file.sync_data()?;
+        self.delta.sync_data()?;
+        self.slots.free.extend(self.released.drain(..));
+
+        Ok(())
+    }
+
+    fn write_changes<K, V>(
+        &mut self,
+        table: &ExtendibleHash<K, V>,
+        buckets: &BTreeSet<usize>,
+        directory: &[usize],
+        sync: bool,
+    ) -> Result<()>
+    where
+        K: Hash + PartialEq + Clone + Debug,
+        V: Keyed<Key = K> + Encode,
+    {
+        check_shape(table)?;
+
+        if self.torn {
+            self.delta.set_len(self.delta_len)?;
+            self.torn = false;
+        }
+
+        // Se a entrada nao for escrita os slots tomados continuam livres
+        let slots = self.slots.clone();
+        let written = match self.write_buckets(table, buckets.iter().copied()) {
+            Ok(written) => written,
+            Err(e) => {
+                self.slots = slots;
+                return Err(e);
+            }
+        };
+
+        let pages: Vec<(usize, u32)> = written.iter().map(|(b, slot, _)| (*b, *slot)).collect();
+        let entry = encode_entry(table, directory, &pages);
+
+        if let Err(e) = self.append(&entry, sync) {
+            self.slots = slots;
+            return Err(e);
+        }
+
+        self.delta_len += entry.len() as u64;
+        self.shape = shape(table);
+        self.commit(written, table.buckets.len());
+
+        if sync {
+            self.delta.sync_data()?;
+            self.slots.free.extend(self.released.drain(..));
+        }
+
+        if self.delta_len - self.base_len > full_entry_len(table) {
+            self.compact(table)?;
+        }
+
+        Ok(())
+    }
+
+    /// Appends `entry` to the delta, after forcing the pages it points to
+    /// to the disk if `sync`.
+    fn append(&mut self, entry: &[u8], sync: bool) -> Result<()> {
+        if sync {
+            self.file.sync_data()?;
+        }
+
+        if let Err(e) = self.delta.write_all(entry) {
+            // Uma entrada cortada esconderia as seguintes
+            self.torn = true;
+
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    /// Starts the delta again with one entry: the shape, the whole
+    /// directory and the slot of every bucket. The slots the old entries
+    /// pointed to are free after it, and the free ones at the end of the
+    /// file are cut.
+    fn compact<K, V>(&mut self, table: &ExtendibleHash<K, V>) -> Result<()>
+    where
+        K: Hash + PartialEq + Clone + Debug,
+        V: Keyed<Key = K> + Encode,
+    {
+        // As paginas para onde a entrada aponta antes dela
+        self.file.sync_data()?;
+
+        let pages: Vec<(usize, u32)> = self.location.iter().copied().enumerate().collect();
+        let directory: Vec<usize> = (0..table.directory().len()).collect();
+
+        self.replace_delta(&encode_entry(table, &directory, &pages))?;
+        self.slots.free.extend(self.released.drain(..));
+
+        while self.slots.len > 0 && self.slots.free.remove(&(self.slots.len - 1)) {
+            self.slots.len -= 1;
+        }
+
+        self.file.set_len(self.offset(self.slots.len))?;
+
+        Ok(())
+    }
+
+    /// Writes the pages of `buckets` to free slots, returning the slot of
+    /// each one and the slots of its overflow pages.
+    fn write_buckets<K, V: Encode>(
+        &mut self,
+        table: &ExtendibleHash<K, V>,
+        buckets: impl Iterator<Item = usize>,
+    ) -> Result<Vec<(usize, u32, Vec<u32>)>> {
+        let mut written = Vec::new();
+
+        for b in buckets {
+            let mut overflow = Overflow {
+                slots: &mut self.slots,
+                pages: Vec::new(),
+            };
+
+            let page = table.buckets[b].serialize(&table.layout, &mut overflow)?;
+            let spill = overflow.pages;
+            let slot = self.slots.take()?;
+
+            for (s, p) in &spill {
+                self.write_page(*s, p)?;
+            }
+
+            self.write_page(slot, &page)?;
+            written.push((b, slot, spill.into_iter().map(|(s, _)| s).collect()));
+        }
+
+        Ok(written)
+    }
+
+    /// Points the buckets written to their new slots, releasing the old
+    /// ones and those of the buckets past the end of the table.
+    fn commit(&mut self, written: Vec<(usize, u32, Vec<u32>)>, buckets: usize) {
+        for (b, slot, spill) in written {
+            if b == self.location.len() {
+                self.location.push(slot);
+            } else {
+                let old = std::mem::replace(&mut self.location[b], slot);
+                self.released.push(old);
+            }
+
+            self.released
+                .extend(self.spilled.remove(&b).into_iter().flatten());
+
+            if !spill.is_empty() {
+                self.spilled.insert(b, spill);
+            }
+        }
+
+        // Baldes descartados no fim
+        self.released.extend(self.location.drain(buckets..));
+
+        for (_, spill) in self.spilled.split_off(&buckets) {
+            self.released.extend(spill);
+        }
+    }
+
+    /// Writes `entry` as the whole delta, replacing the old one at once.
+    fn replace_delta(&mut self, entry: &[u8]) -> Result<()> {
+        let path = delta_path(&self.path);
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        let mut f = File::create(&tmp)?;
+        f.write_all(entry)?;
+        f.sync_all()?;
+        std::fs::rename(&tmp, &path)?;
+
+        self.delta = OpenOptions::new().read(true).append(true).open(&path)?;
+        self.delta_len = entry.len() as u64;
+        self.base_len = self.delta_len;
+        self.torn = false;
+
+        Ok(())
+    }
+
+    /// Each page is followed by its CRC-32.
+    fn offset(&self, slot: u32) -> u64 {
+        self.data_start + slot as u64 * (self.page_size as u64 + 4)
+    }
+
+    fn write_page(&mut self, slot: u32, page: &[u8]) -> Result<()> {
+        let mut buf = page.to_vec();
+        buf.extend_from_slice(&crc32(page).to_be_bytes());
+
+        self.file.seek(SeekFrom::Start(self.offset(slot)))?;
+        self.file.write_all(&buf)?;
+
+        Ok(())
+    }
+
+    fn read_page(&mut self, slot: u32) -> Result<Vec<u8>> {
+        let offset = self.offset(slot);
+        let mut page = vec![0; self.page_size];
+
+        self.file.seek(SeekFrom::Start(offset))?;
+
+        let mut r = Reader::at(&mut self.file, offset);
+        r.read_exact(&mut page)?;
+        r.verify_checksum(offset, format!("pagina {slot}"))?;
+
+        Ok(page)
+    }
+}
+
+/// `{path}.delta`
+fn delta_path(path: &Path) -> PathBuf {
+    let mut delta: OsString = path.as_os_str().to_owned();
+    delta.push(".delta");
+
+    delta.into()
+}
+
+fn shape<K, V>(table: &ExtendibleHash<K, V>) -> Shape
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode,
+{
+    (
+        table.global_depth(),
+        table.max_global_depth(),
+        table.next_rid,
+        table.buckets.len() as u32,
+    )
+}
+
+fn check_shape<K, V: Encode>(table: &ExtendibleHash<K, V>) -> Result<()> {
+    // u32::MAX marca balde sem overflow
+    if table.buckets.len() >= u32::MAX as usize {
+        return Err(Error::TooManyBuckets(table.buckets.len()));
+    }
+
+    Ok(())
+}
+
+/// Length of the entry `compact` leaves in the delta for `table`.
+fn full_entry_len<K, V: Encode>(table: &ExtendibleHash<K, V>) -> u64 {
+    let head = 4 + 1 + 1 + 8 + 4;
+    let directory = 4 + 4 + 4 + 4 * table.directory().len();
+    let pages = 4 + 8 * table.buckets.len();
+
+    (head + directory + pages + 4) as u64
+}
+
+fn encode_entry<K, V>(
+    table: &ExtendibleHash<K, V>,
+    directory: &[usize],
+    pages: &[(usize, u32)],
+) -> Vec<u8>
+where
+    K: Hash + PartialEq + Clone + Debug,
+    V: Keyed<Key = K> + Encode,
+{
+    let (global_depth, max_global_depth, next_rid, buckets) = shape(table);
+    let mut buf = vec![0; 4];
+
+    buf.push(global_depth);
+    buf.push(max_global_depth);
+    buf.extend_from_slice(&(next_rid as u64).to_be_bytes());
+    buf.extend_from_slice(&buckets.to_be_bytes());
+
+    // Entradas consecutivas do diretorio viram uma faixa
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+
+    for i in directory {
+        match ranges.last_mut() {
+            Some((_, end)) if *end == *i => *end += 1,
+            _ => ranges.push((*i, *i + 1)),
+        }
+    }
+
+    buf.extend_from_slice(&(ranges.len() as u32).to_be_bytes());
+
+    for (start, end) in ranges {
+        buf.extend_from_slice(&(start as u32).to_be_bytes());
+        buf.extend_from_slice(&((end - start) as u32).to_be_bytes());
+
+        for d in &table.directory()[start..end] {
+            buf.extend_from_slice(&(*d as u32).to_be_bytes());
+        }
+    }
+
+    buf.extend_from_slice(&(pages.len() as u32).to_be_bytes());
+
+    for (b, slot) in pages {
+        buf.extend_from_slice(&(*b as u32).to_be_bytes());
+        buf.extend_from_slice(&slot.to_be_bytes());
+    }
+
+    let len = (buf.len() - 4) as u32;
+    buf[..4].copy_from_slice(&len.to_be_bytes());
+
+    let crc = crc32(&buf);
+    buf.extend_from_slice(&crc.to_be_bytes());
+
+    buf
+}
+
+/// An entry of the delta, decoded.
+struct Entry {
+    shape: Shape,
+    ranges: Ranges,
+    /// Slot of each bucket written.
+    pages: Vec<(usize, u32)>,
+}
+
+/// Applies to `directory` the ranges of the entry at `offset` of the delta.
+fn replay(entry: &Entry, offset: usize, directory: &mut Vec<usize>) -> Result<()> {
+    let (global_depth, ..) = entry.shape;
+
+    if global_depth > MAX_GLOBAL_DEPTH {
+        return Err(Error::Corrupt {
+            offset: offset as u64,
+            reason: format!("global depth {global_depth} invalida"),
+        });
+    }
+
+    directory.resize(1 << global_depth, 0);
+
+    for (start, entries) in &entry.ranges {
+        let Some(range) = directory.get_mut(*start..start + entries.len()) else {
+            return Err(Error::Corrupt {
+                offset: offset as u64,
+                reason: format!("faixa do diretorio a partir de {start} fora dele"),
+            });
+        };
+
+        range.copy_from_slice(entries);
+    }
+
+    Ok(())
+}
+
+/// The entry at the start of `bytes` and its length, or `None` if it is
+/// torn or damaged.
+fn decode_entry(bytes: &[u8]) -> Option<(Entry, usize)> {
+    let (mut r, len) = open_entry(bytes)?;
+    let (shape, ranges) = decode_head(&mut r)?;
+
+    let pages = (0..r.read_u32().ok()?)
+        .map(|_| Some((r.read_u32().ok()? as usize, r.read_u32().ok()?)))
+        .collect::<Option<_>>()?;
+
+    let entry = Entry {
+        shape,
+        ranges,
+        pages,
+    };
+
+    Some((entry, len))
+}
+
+/// A reader over the entry at the start of `bytes`, without its length and
+/// CRC, and the length of the whole entry. `None` if it is torn or damaged.
+fn open_entry(bytes: &[u8]) -> Option<(Reader<&[u8]>, usize)> {
+    let len = u32::from_be_bytes(bytes.get(..4)?.try_into().unwrap()) as usize;
+    let end = len.checked_add(4)?;
+    let crc = u32::from_be_bytes(bytes.get(end..end.checked_add(4)?)?.try_into().unwrap());
+
+    if crc32(&bytes[..end]) != crc {
+        return None;
+    }
+
+    Some((Reader::new(&bytes[4..end]), end + 4))
+}
+
+/// The shape and the directory ranges at the start of an entry.
+fn decode_head(r: &mut Reader<&[u8]>) -> Option<(Shape, Ranges)> {
+    let shape = (
+        r.read_u8().ok()?,
+        r.read_u8().ok()?,
+        bucket::read_rid(r).ok()?,
+        r.read_u32().ok()?,
+    );
+
+    let mut ranges = Vec::new();
+
+    for _ in 0..r.read_u32().ok()? {
+        let start = r.read_u32().ok()? as usize;
+        let n = r.read_u32().ok()?;
+        let entries = (0..n)
+            .map(|_| r.read_u32().map(|d| d as usize))
+            .collect::<Result<_>>()
+            .ok()?;
+
+        ranges.push((start, entries));
+    }
+
+    Some((shape, ranges))
+}
+
+/// Slots of the page file: how many there are and which are free.
+#[derive(Clone, Default)]
+struct Slots {
+    len: u32,
+    free: BTreeSet<u32>,
+}
+
+impl Slots {
+    /// The lowest free slot, or a new one at the end of the file.
+    fn take(&mut self) -> Result<u32> {
+        if let Some(slot) = self.free.pop_first() {
+            return Ok(slot);
+        }
+
+        // NONE marca balde sem pagina
+        if self.len >= NONE - 1 {
+            return Err(Error::TooManyBuckets(self.len as usize));
+        }
+
+        self.len += 1;
+
+        Ok(self.len - 1)
+    }
+}
+
+/// Overflow pages of the bucket being written, each in a free slot.
+struct Overflow<'a> {
+    slots: &'a mut Slots,
+    pages: Vec<(u32, Vec<u8>)>,
+}
+
+impl SpillPages for Overflow<'_> {
+    fn write_page(&mut self, page: Vec<u8>) -> Result<u32> {
+        let slot = self.slots.take()?;
+        self.pages.push((slot, page));
+
+        Ok(slot)
+    }
+
+    fn read_page(&mut self, _id: u32) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+}
+
+/// Overflow pages read from the page file, noting their slots.
+struct SlotReader<'a> {
+    store: &'a mut Store,
+    read: Vec<u32>,
+}
+
+impl SpillPages for SlotReader<'_> {
+    fn write_page(&mut self, _page: Vec<u8>) -> Result<u32> {
+        unreachable!("paginas de overflow so sao lidas ao abrir")
+    }
+
+    fn read_page(&mut self, id: u32) -> Result<Option<Vec<u8>>> {
+        if id >= self.store.slots.len {
+            return Ok(None);
+        }
+
+        self.read.push(id);
+        self.store.read_page(id).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{hash_alt1::HashAlt1, record::Record, temp_path};
+
+    fn record(nseq: i32, len: usize) -> Record {
+        Record {
+            nseq,
+            text: "x".repeat(len),
+        }
+    }
+
+    /// Records of the table in key order.
+    fn contents(h: &HashAlt1) -> Vec<(i32, String, Rid)> {
+        let mut records: Vec<_> = h
+            .iter()
+            .map(|r| (r.nseq, r.text.clone(), h.search(&r.nseq).unwrap()))
+            .collect();
+        records.sort();
+        records
+    }
+
+    fn delta_len(path: &str) -> u64 {
+        file_len(&format!("{path}.delta"))
+    }
+
+    fn file_len(path: &str) -> u64 {
+        std::fs::metadata(path).unwrap().len()
+    }
+
+    #[test]
+    fn test_flush_only_changes() {
+        let path = temp_path("store_flush.pages");
+        let mut h = HashAlt1::new(4, 2).unwrap();
+        h.set_merge_threshold(0.0).unwrap();
+
+        for nseq in 0..32 {
+            h.insert(record(nseq, 10)).unwrap();
+        }
+
+        let mut store = Store::create(&path, &mut h).unwrap();
+        let before = delta_len(&path);
+        let pages = file_len(&path);
+
+        // Um balde e o proximo rid, sem mudar o diretorio: a pagina vai para
+        // um slot novo e a entrada so diz qual
+        h.remove(&5).unwrap();
+        h.insert(record(5, 10)).unwrap();
+        store.flush(&mut h, true).unwrap();
+
+        let entry = 4 + 1 + 1 + 8 + 4 + 4 + 4 + 4 + 4 + 4;
+        assert_eq!(delta_len(&path) - before, entry as u64);
+        assert_eq!(file_len(&path), pages + h.page_size() as u64 + 4);
+
+        // Sem mudancas nada e escrito
+        store.flush(&mut h, true).unwrap();
+        assert_eq!(delta_len(&path) - before, entry as u64);
+
+        // O slot antigo e reaproveitado depois do sync
+        h.remove(&5).unwrap();
+        h.insert(record(5, 10)).unwrap();
+        store.flush(&mut h, true).unwrap();
+
+        assert_eq!(file_len(&path), pages + h.page_size() as u64 + 4);
+
+        drop(store);
+
+        let (_, h2): (Store, HashAlt1) = Store::open(&path).unwrap();
+
+        assert_eq!(contents(&h2), contents(&h));
+        assert_eq!(h2.directory(), h.directory());
+        assert_eq!(h2.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_reopen_after_changes() {
+        let path = temp_path("store_reopen.pages");
+        let mut h = HashAlt1::new(1, 2).unwrap();
+        let mut store = Store::create(&path, &mut h).unwrap();
+
+        // Divisoes, juncoes, registros grandes e compactacoes do delta
+        for round in 0..6 {
+            for nseq in 0..60 {
+                let len = if nseq % 13 == 0 { 400 + round * 50 } else { 10 };
+                h.upsert(record(nseq + round as i32 * 20, len)).unwrap();
+            }
+
+            h.retain(|r| r.nseq % (round as i32 + 2) != 0);
+            store.flush(&mut h, round % 2 == 0).unwrap();
+
+            let (reopened, h2): (Store, HashAlt1) = Store::open(&path).unwrap();
+
+            assert_eq!(contents(&h2), contents(&h), "rodada {round}");
+            assert_eq!(h2.directory(), h.directory());
+            assert_eq!(h2.check_invariants(), Ok(()));
+
+            // Continua de onde o arquivo parou
+            store = reopened;
+            h = h2;
+        }
+
+        // O delta foi compactado
+        assert!(store.delta_len - store.base_len <= full_entry_len(&h));
+        assert!(store.base_len > 0);
+
+        h.drain();
+        store.flush(&mut h, true).unwrap();
+
+        let (_, h2): (Store, HashAlt1) = Store::open(&path).unwrap();
+
+        assert!(h2.is_empty());
+        assert_eq!(h2.global_depth(), 0);
+    }
+
+    #[test]
+    fn test_torn_entry() {
+        let path = temp_path("store_torn.pages");
+        let mut h = HashAlt1::new(4, 2).unwrap();
+
+        for nseq in 0..10 {
+            h.insert(record(nseq, 10)).unwrap();
+        }
+
+        let mut store = Store::create(&path, &mut h).unwrap();
+        let expected = contents(&h);
+        let before = delta_len(&path);
+
+        for nseq in 10..20 {
+            h.insert(record(nseq, 10)).unwrap();
+        }
+
+        store.flush(&mut h, true).unwrap();
+        drop(store);
+
+        // Crash no meio da entrada: as paginas novas ficam em slots livres
+        let full = delta_len(&path);
+        let f = OpenOptions::new()
+            .write(true)
+            .open(format!("{path}.delta"))
+            .unwrap();
+        f.set_len((before + full) / 2).unwrap();
+
+        let (mut store, mut h2): (Store, HashAlt1) = Store::open(&path).unwrap();
+
+        assert_eq!(contents(&h2), expected);
+        assert_eq!(delta_len(&path), before);
+
+        h2.insert(record(50, 10)).unwrap();
+        store.flush(&mut h2, true).unwrap();
+
+        let (_, h3): (Store, HashAlt1) = Store::open(&path).unwrap();
+
+        assert_eq!(h3.len(), 11);
+
+        // Sem o delta o store esta incompleto
+        std::fs::remove_file(format!("{path}.delta")).unwrap();
+
+        assert!(matches!(
+            Store::open::<_, i32, Record>(&path),
+            Err(Error::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,1058 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    error::{Error, Result},
+    extendible_hash::Rid,
+    format,
+    hash_alt1::HashAlt1,
+    record::Record,
+    store::Store,
+    wal::{LogRecord, SyncPolicy, Wal},
+};
+
+/// An index over the records of a `Table`, kept in sync by it.
+pub trait SecondaryIndex {
+    /// Adds the entry of the record stored under `rid`.
+    fn insert(&mut self, rid: Rid, record: &Record) -> Result<()>;
+
+    fn remove(&mut self, rid: Rid, record: &Record) -> Result<()>;
+
+    /// Moves the entry of a record updated in place. If the new entry is
+    /// rejected the old one is put back.
+    fn update(&mut self, rid: Rid, old: &Record, new: &Record) -> Result<()> {
+        self.remove(rid, old)?;
+
+        if let Err(e) = self.insert(rid, new) {
+            self.insert(rid, old)?;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Rids of the records whose key in this index is the key of `probe`.
+    fn lookup(&self, probe: &Record) -> Vec<Rid>;
+
+    /// Number of entries.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn clear(&mut self);
+
+    fn check_invariants(&self) -> std::result::Result<(), String>;
+
+    fn write_to(&self, w: &mut dyn Write) -> Result<()>;
+
+    /// Replaces the entries with the ones written by `write_to`.
+    fn read_from(&mut self, r: &mut dyn Read) -> Result<()>;
+
+    /// Writes the index whole to a new `Store` at `path`.
+    fn create_store(&mut self, path: &str) -> Result<Store>;
+
+    /// Replaces the entries with the ones in the `Store` at `path`.
+    fn open_store(&mut self, path: &str) -> Result<Store>;
+
+    /// Writes the entries changed since the last flush to `store`, forcing
+    /// them to the disk if `sync`.
+    fn flush(&mut self, store: &mut Store, sync: bool) -> Result<()>;
+}
+
+/// The records, stored in a `HashAlt1` by `nseq`, plus the secondary indexes
+/// registered on them. Every change is applied to all of them or to none.
+///
+/// After `recover` or `start_log` every change is also written to a
+/// `Wal` before it returns, so a crash loses nothing since the last
+/// `sync` that the sync policy promised to keep. `flush` and `sync` write
+/// only the buckets and directory entries that changed, each table to its
+/// `Store` under the same prefix.
+pub struct Table {
+    records: HashAlt1,
+    indexes: Vec<(String, Box<dyn SecondaryIndex>)>,
+    wal: Option<Wal>,
+    /// Prefix of the stores, after `recover` or `start_log`.
+    prefix: Option<String>,
+    /// Store of the records, then one per index, in order.
+    stores: Vec<Store>,
+}
+
+impl Table {
+    pub fn new(records: HashAlt1) -> Self {
+        Table {
+            records,
+            indexes: Vec::new(),
+            wal: None,
+            prefix: None,
+            stores: Vec::new(),
+        }
+    }
+
+    pub fn records(&self) -> &HashAlt1 {
+        &self.records
+    }
+
+    pub fn index(&self, name: &str) -> Result<&dyn SecondaryIndex> {
+        self.indexes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, i)| i.as_ref())
+            .ok_or_else(|| Error::IndexNotFound(name.to_string()))
+    }
+
+    pub fn index_names(&self) -> impl Iterator<Item = &str> {
+        self.indexes.iter().map(|(n, _)| n.as_str())
+    }
+
+    /// Adds an index and fills it with the records already in the table,
+    /// writing it to a new store if the table has them.
+    pub fn register(&mut self, name: &str, mut index: Box<dyn SecondaryIndex>) -> Result<()> {
+        if self.index(name).is_ok() {
+            return Err(Error::DuplicateKey(name.to_string()));
+        }
+
+        fill(&self.records, index.as_mut())?;
+
+        if let Some(prefix) = &self.prefix {
+            self.stores
+                .push(index.create_store(&format!("{prefix}_{name}.pages"))?);
+        }
+
+        self.indexes.push((name.to_string(), index));
+
+        if let Some(prefix) = &self.prefix {
+            self.write_names(prefix)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, nseq: i32) -> Result<&Record> {
+        Ok(self.records.get(self.records.search(&nseq)?).unwrap())
+    }
+
+    /// Records with the same key as `probe` in the index `name`.
+    pub fn find(&self, name: &str, probe: &Record) -> Result<Vec<&Record>> {
+        Ok(self
+            .index(name)?
+            .lookup(probe)
+            .into_iter()
+            .filter_map(|rid| self.records.get(rid))
+            .collect())
+    }
+
+    pub fn insert(&mut self, record: Record) -> Result<Rid> {
+        let rid = self.apply_insert(record.clone())?;
+
+        // Sem o registro no log a mudanca nao aconteceu
+        if let Err(e) = self.log(LogRecord::Insert(record.clone())) {
+            self.apply_remove(record.nseq)?;
+            return Err(e);
+        }
+
+        Ok(rid)
+    }
+
+    /// Replaces the record with the same `nseq` in place, returning the old one.
+    pub fn update(&mut self, record: Record) -> Result<Record> {
+        let old = self.apply_update(record.clone())?;
+
+        if let Err(e) = self.log(LogRecord::Update(record)) {
+            self.apply_update(old)?;
+            return Err(e);
+        }
+
+        Ok(old)
+    }
+
+    pub fn remove(&mut self, nseq: i32) -> Result<Record> {
+        let rid = self.records.search(&nseq)?;
+        let record = self.apply_remove(nseq)?;
+
+        // Volta com o mesmo rid, que pode estar guardado fora da tabela
+        if let Err(e) = self.log(LogRecord::Remove(nseq)) {
+            self.records.reinsert(rid, record.clone())?;
+            self.insert_indexes(rid)?;
+            return Err(e);
+        }
+
+        Ok(record)
+    }
+
+    fn apply_insert(&mut self, record: Record) -> Result<Rid> {
+        let rid = self.records.insert(record)?;
+        self.insert_indexes(rid)?;
+
+        Ok(rid)
+    }
+
+    /// Adds the record `rid` of the primary to every index. If one rejects
+    /// it, it is taken out of the others and of the primary.
+    fn insert_indexes(&mut self, rid: Rid) -> Result<()> {
+        let record = self.records.get(rid).unwrap().clone();
+
+        for i in 0..self.indexes.len() {
+            if let Err(e) = self.indexes[i].1.insert(rid, &record) {
+                // Desfaz tudo mesmo que um passo falhe; vale o primeiro erro
+                for (_, index) in &mut self.indexes[..i] {
+                    let _ = index.remove(rid, &record);
+                }
+                let _ = self.records.remove_rid(rid);
+
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_update(&mut self, record: Record) -> Result<Record> {
+        let rid = self.records.search(&record.nseq)?;
+        let old = self.records.replace(record.clone())?;
+
+        for i in 0..self.indexes.len() {
+            if let Err(e) = self.indexes[i].1.update(rid, &old, &record) {
+                for (_, index) in &mut self.indexes[..i] {
+                    let _ = index.update(rid, &record, &old);
+                }
+                let _ = self.records.replace(old);
+
+                return Err(e);
+            }
+        }
+
+        Ok(old)
+    }
+
+    fn apply_remove(&mut self, nseq: i32) -> Result<Record> {
+        let rid = self.records.search(&nseq)?;
+        let record = self.records.get(rid).unwrap().clone();
+
+        for i in 0..self.indexes.len() {
+            if let Err(e) = self.indexes[i].1.remove(rid, &record) {
+                for (_, index) in &mut self.indexes[..i] {
+                    let _ = index.insert(rid, &record);
+                }
+
+                return Err(e);
+            }
+        }
+
+        self.records.remove_rid(rid)
+    }
+
+    /// Appends `change` to the log, after the splits and doublings of the
+    /// primary index it caused. Merges are not logged: `redo` makes them
+    /// again with the removes.
+    fn log(&mut self, change: LogRecord) -> Result<()> {
+        let mut records = self.records.take_structure();
+        let Some(wal) = &mut self.wal else {
+            return Ok(());
+        };
+
+        records.push(change);
+        wal.append(&records)?;
+
+        Ok(())
+    }
+
+    /// Applies a logged change again. The table may already have it, when
+    /// a checkpoint saved the files but did not get to empty the log, so
+    /// inserts of keys already in the table and updates and removes of keys
+    /// not in it are skipped, as are splits and doublings the table already
+    /// has. Returns whether a change was applied.
+    fn redo(&mut self, change: LogRecord) -> Result<bool> {
+        let found = |t: &Table, nseq: i32| t.records.search(&nseq).is_ok();
+
+        match change {
+            LogRecord::Insert(r) if !found(self, r.nseq) => self.apply_insert(r).map(|_| true),
+            LogRecord::Update(r) if found(self, r.nseq) => self.apply_update(r).map(|_| true),
+            LogRecord::Remove(nseq) if found(self, nseq) => self.apply_remove(nseq).map(|_| true),
+            LogRecord::Split { .. } | LogRecord::Doubling { .. } => {
+                self.records.redo_structure(&change);
+                Ok(false)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Opens the stores under `prefix`, or writes new ones from the files
+    /// of `save` or the table as it is, applies the changes in
+    /// `{prefix}.wal` again and keeps logging to it. Returns the number of
+    /// changes applied.
+    pub fn recover(&mut self, prefix: &str, policy: SyncPolicy) -> Result<usize> {
+        if Path::new(&format!("{prefix}_alt1.pages.delta")).exists() {
+            self.open_stores(prefix)?;
+        } else {
+            if Path::new(&format!("{prefix}_alt1.bin")).exists() {
+                self.load(prefix)?;
+            }
+
+            self.create_stores(prefix)?;
+        }
+
+        let (wal, records) = Wal::open(format!("{prefix}.wal"), policy)?;
+        let mut applied = 0;
+
+        for (_, change) in records {
+            if self.redo(change)? {
+                applied += 1;
+            }
+        }
+
+        // Um sync interrompido pode ter escrito o primario sem os indices
+        if self.check_consistency().is_err() {
+            self.rebuild()?;
+        }
+
+        // O que o redo dividiu ja esta no log
+        self.records.log_structure();
+        self.wal = Some(wal);
+
+        Ok(applied)
+    }
+
+    /// Writes the table whole to new stores under `prefix` and starts
+    /// logging its changes to `{prefix}.wal`, dropping whatever was logged
+    /// there before.
+    pub fn start_log(&mut self, prefix: &str, policy: SyncPolicy) -> Result<()> {
+        let (wal, _) = Wal::open(format!("{prefix}.wal"), policy)?;
+
+        self.records.log_structure();
+        self.wal = Some(wal);
+        self.create_stores(prefix)?;
+        self.sync()
+    }
+
+    /// Writes the buckets and directory entries changed since the last
+    /// flush to the stores, leaving it to the operating system to put them
+    /// on the disk. Does nothing before `recover` or `start_log`.
+    pub fn flush(&mut self) -> Result<()> {
+        self.flush_stores(false)
+    }
+
+    /// Flushes, waits for the stores to be on the disk and empties the log.
+    pub fn sync(&mut self) -> Result<()> {
+        self.flush_stores(true)?;
+
+        if let Some(wal) = &mut self.wal {
+            wal.reset()?;
+            // Ja estao nos arquivos
+            self.records.take_structure();
+        }
+
+        Ok(())
+    }
+
+    fn flush_stores(&mut self, sync: bool) -> Result<()> {
+        let Some((records, indexes)) = self.stores.split_first_mut() else {
+            return Ok(());
+        };
+
+        records.flush(&mut self.records, sync)?;
+
+        for ((_, index), store) in self.indexes.iter_mut().zip(indexes) {
+            index.flush(store, sync)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `{prefix}_alt1.pages` and one `{prefix}_{name}.pages` per
+    /// index, plus the index names, and flushes to them from then on.
+    fn create_stores(&mut self, prefix: &str) -> Result<()> {
+        let mut stores = vec![Store::create(
+            format!("{prefix}_alt1.pages"),
+            &mut self.records,
+        )?];
+
+        for (name, index) in &mut self.indexes {
+            stores.push(index.create_store(&format!("{prefix}_{name}.pages"))?);
+        }
+
+        self.write_names(prefix)?;
+        self.prefix = Some(prefix.to_string());
+        self.stores = stores;
+
+        Ok(())
+    }
+
+    /// Reads the stores written by `create_stores`, like `load`. A
+    /// registered index without a store is rebuilt and gets a new one.
+    fn open_stores(&mut self, prefix: &str) -> Result<()> {
+        self.check_saved_indexes(prefix)?;
+
+        let (store, records) = Store::open(format!("{prefix}_alt1.pages"))?;
+        let mut stores = vec![store];
+
+        self.records = records;
+
+        for (name, index) in &mut self.indexes {
+            let path = format!("{prefix}_{name}.pages");
+
+            stores.push(if Path::new(&format!("{path}.delta")).exists() {
+                index.open_store(&path)?
+            } else {
+                fill(&self.records, index.as_mut())?;
+                index.create_store(&path)?
+            });
+        }
+
+        self.write_names(prefix)?;
+        self.prefix = Some(prefix.to_string());
+        self.stores = stores;
+
+        Ok(())
+    }
+
+    pub fn wal(&self) -> Option<&Wal> {
+        self.wal.as_ref()
+    }
+
+    /// Removes the records with the same key as `probe` in the index `name`.
+    pub fn remove_by(&mut self, name: &str, probe: &Record) -> Result<Vec<Record>> {
+        let nseqs: Vec<i32> = self.find(name, probe)?.iter().map(|r| r.nseq).collect();
+
+        if nseqs.is_empty() {
+            return Err(Error::KeyNotFound(format!("{probe:?}")));
+        }
+
+        nseqs.into_iter().map(|nseq| self.remove(nseq)).collect()
+    }
+
+    /// Refills every secondary index from the records.
+    pub fn rebuild(&mut self) -> Result<()> {
+        for (_, index) in &mut self.indexes {
+            fill(&self.records, index.as_mut())?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks every index on its own and that each one has exactly one
+    /// entry per record.
+    pub fn check_consistency(&self) -> std::result::Result<(), String> {
+        self.records
+            .check_invariants()
+            .map_err(|e| format!("Primary index: {e}"))?;
+
+        for (name, index) in &self.indexes {
+            index
+                .check_invariants()
+                .map_err(|e| format!("Index {name}: {e}"))?;
+
+            if index.len() != self.records.len() {
+                return Err(format!(
+                    "Index {name} has {} entries for {} records",
+                    index.len(),
+                    self.records.len()
+                ));
+            }
+
+            for r in self.records.iter() {
+                let rid = self.records.search(&r.nseq).unwrap();
+
+                if !index.lookup(r).contains(&rid) {
+                    return Err(format!("Index {name} is missing record {}", r.nseq));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes `{prefix}_alt1.bin`, one `{prefix}_{name}.bin` per index and
+    /// the index names, one per line, in `{prefix}_indexes.txt`. Each file is
+    /// replaced whole, so a crash leaves either the old one or the new one.
+    pub fn save(&self, prefix: &str) -> Result<()> {
+        write_atomic(&format!("{prefix}_alt1.bin"), |w| self.records.write_to(w))?;
+
+        for (name, index) in &self.indexes {
+            write_atomic(&format!("{prefix}_{name}.bin"), |w| index.write_to(w))?;
+        }
+
+        self.write_names(prefix)
+    }
+
+    fn write_names(&self, prefix: &str) -> Result<()> {
+        let names: Vec<&str> = self.index_names().collect();
+
+        write_atomic(&format!("{prefix}_indexes.txt"), |w| {
+            Ok(w.write_all(names.join("\n").as_bytes())?)
+        })
+    }
+
+    /// Names of the indexes written by `save` or kept in stores. Key
+    /// functions are not saved, so each one must be registered again before
+    /// `load` or `recover`.
+    pub fn saved_indexes(prefix: &str) -> Result<Vec<String>> {
+        let names = std::fs::read_to_string(format!("{prefix}_indexes.txt"))?;
+
+        Ok(names.lines().map(String::from).collect())
+    }
+
+    /// Reads the files written by `save` into a table with the same indexes
+    /// registered, failing with `IndexNotFound` if a saved one is missing.
+    /// A registered index without a file is rebuilt from the records, and so
+    /// are all of them when the records are in a headerless file of the
+    /// first releases. A table with stores writes them again whole.
+    pub fn load(&mut self, prefix: &str) -> Result<()> {
+        self.check_saved_indexes(prefix)?;
+
+        let mut r = BufReader::new(File::open(format!("{prefix}_alt1.bin"))?);
+
+        // Os indices desses arquivos guardavam o balde e o slot dos
+        // registros, que mudam ao rele-los
+        let legacy = r
+            .fill_buf()?
+            .first()
+            .is_some_and(|b| format::is_legacy(*b, format::TABLE_MAGIC));
+
+        self.records = HashAlt1::read_from(&mut r)?;
+
+        if self.wal.is_some() {
+            self.records.log_structure();
+        }
+
+        for (name, index) in &mut self.indexes {
+            match File::open(format!("{prefix}_{name}.bin")) {
+                Ok(f) if !legacy => index.read_from(&mut BufReader::new(f))?,
+                _ => fill(&self.records, index.as_mut())?,
+            }
+        }
+
+        if let Some(prefix) = self.prefix.clone() {
+            self.create_stores(&prefix)?;
+        }
+
+        Ok(())
+    }
+
+    fn check_saved_indexes(&self, prefix: &str) -> Result<()> {
+        if let Ok(names) = Self::saved_indexes(prefix) {
+            if let Some(name) = names.iter().find(|n| self.index(n).is_err()) {
+                return Err(Error::IndexNotFound(name.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes a temporary file with `write` and renames it over `path`.
+fn write_atomic(path: &str, write: impl FnOnce(&mut BufWriter<File>) -> Result<()>) -> Result<()> {
+    let tmp = format!("{path}.tmp");
+    let mut w = BufWriter::new(File::create(&tmp)?);
+
+    write(&mut w)?;
+    let f = w.into_inner().map_err(|e| e.into_error())?;
+    f.sync_all()?;
+    std::fs::rename(tmp, path)?;
+
+    Ok(())
+}
+
+fn fill(records: &HashAlt1, index: &mut dyn SecondaryIndex) -> Result<()> {
+    index.clear();
+
+    for r in records.iter() {
+        index.insert(records.search(&r.nseq)?, r)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Command, Stdio},
+    };
+
+    use rand::Rng;
+
+    use super::*;
+    use crate::{hash_alt2::HashAlt2, hash_text::HashText, temp_path, wal};
+
+    /// Index over `nseq` that rejects the text `fail` and forgets `lose`.
+    struct Flaky {
+        entries: Vec<(Rid, i32)>,
+        fail: &'static str,
+        lose: i32,
+    }
+
+    impl SecondaryIndex for Flaky {
+        fn insert(&mut self, rid: Rid, record: &Record) -> Result<()> {
+            if record.text == self.fail {
+                return Err(Error::DuplicateKey(record.text.clone()));
+            }
+
+            if record.nseq != self.lose {
+                self.entries.push((rid, record.nseq));
+            }
+
+            Ok(())
+        }
+
+        fn remove(&mut self, rid: Rid, _record: &Record) -> Result<()> {
+            self.entries.retain(|e| e.0 != rid);
+
+            Ok(())
+        }
+
+        fn lookup(&self, probe: &Record) -> Vec<Rid> {
+            self.entries
+                .iter()
+                .filter(|e| e.1 == probe.nseq)
+                .map(|e| e.0)
+                .collect()
+        }
+
+        fn len(&self) -> usize {
+            self.entries.len()
+        }
+
+        fn clear(&mut self) {
+            self.entries.clear();
+        }
+
+        fn check_invariants(&self) -> std::result::Result<(), String> {
+            Ok(())
+        }
+
+        fn write_to(&self, _w: &mut dyn Write) -> Result<()> {
+            Ok(())
+        }
+
+        fn read_from(&mut self, _r: &mut dyn Read) -> Result<()> {
+            Ok(())
+        }
+
+        // Nao e salvo: o store fica vazio
+        fn create_store(&mut self, path: &str) -> Result<Store> {
+            Store::create(path, &mut HashAlt1::new(0, 1).unwrap())
+        }
+
+        fn open_store(&mut self, path: &str) -> Result<Store> {
+            self.create_store(path)
+        }
+
+        fn flush(&mut self, _store: &mut Store, _sync: bool) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn record(nseq: i32, text: &str) -> Record {
+        Record {
+            nseq,
+            text: text.to_string(),
+        }
+    }
+
+    fn table() -> Table {
+        let mut t = Table::new(HashAlt1::new(1, 2).unwrap());
+
+        t.register("alt2", Box::new(HashAlt2::new(1, 2).unwrap()))
+            .unwrap();
+        t.register("text", Box::new(HashText::new(1, 2).unwrap()))
+            .unwrap();
+
+        for nseq in 0..20 {
+            t.insert(record(nseq, &format!("texto {}", nseq % 4)))
+                .unwrap();
+        }
+
+        t
+    }
+
+    #[test]
+    fn test_insert_update_remove() {
+        let mut t = table();
+
+        assert_eq!(t.check_consistency(), Ok(()));
+        assert_eq!(t.find("text", &record(0, "texto 1")).unwrap().len(), 5);
+
+        let old = t.update(record(5, "dolor")).unwrap();
+
+        assert_eq!(old.text, "texto 1");
+        assert_eq!(t.find("text", &record(0, "texto 1")).unwrap().len(), 4);
+        assert_eq!(t.find("alt2", &record(5, "dolor")).unwrap()[0].nseq, 5);
+        assert!(t.find("alt2", &record(5, "texto 1")).unwrap().is_empty());
+        assert_eq!(t.check_consistency(), Ok(()));
+
+        // Remocao pela chave secundaria confere o texto
+        assert!(matches!(
+            t.remove_by("alt2", &record(6, "texto 1")),
+            Err(Error::KeyNotFound(_))
+        ));
+        assert_eq!(t.get(6).unwrap().text, "texto 2");
+
+        t.remove_by("alt2", &record(6, "texto 2")).unwrap();
+        t.remove(7).unwrap();
+
+        assert!(t.get(6).is_err());
+        assert!(t.get(7).is_err());
+        assert_eq!(t.records().len(), 18);
+        assert_eq!(t.check_consistency(), Ok(()));
+
+        let removed = t.remove_by("text", &record(0, "texto 0")).unwrap();
+
+        assert_eq!(removed.len(), 5);
+        assert_eq!(t.check_consistency(), Ok(()));
+        assert!(matches!(
+            t.find("nseq", &record(0, "")),
+            Err(Error::IndexNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_all_or_nothing() {
+        let mut t = table();
+
+        t.register(
+            "flaky",
+            Box::new(Flaky {
+                entries: Vec::new(),
+                fail: "ruim",
+                lose: -1,
+            }),
+        )
+        .unwrap();
+
+        assert!(t.insert(record(30, "ruim")).is_err());
+        assert!(t.get(30).is_err());
+        assert!(t.find("text", &record(0, "ruim")).unwrap().is_empty());
+        assert_eq!(t.check_consistency(), Ok(()));
+
+        // Atualizar para um registro que o indice rejeita desfaz as outras mudancas
+        assert!(t.update(record(1, "ruim")).is_err());
+        assert_eq!(t.get(1).unwrap().text, "texto 1");
+        assert_eq!(t.find("text", &record(0, "texto 1")).unwrap().len(), 5);
+        assert!(t.find("alt2", &record(1, "ruim")).unwrap().is_empty());
+        assert_eq!(t.check_consistency(), Ok(()));
+
+        // Textos longos vao para paginas de overflow em todos os indices
+        t.update(record(1, &"b".repeat(300))).unwrap();
+        assert_eq!(
+            t.find("text", &record(0, &"b".repeat(300))).unwrap().len(),
+            1
+        );
+        assert_eq!(t.check_consistency(), Ok(()));
+    }
+
+    #[test]
+    fn test_log_failure() {
+        let path = temp_path("table_read_only.wal");
+        std::fs::write(&path, []).unwrap();
+
+        let mut t = table();
+        let rid = t.records().search(&3).unwrap();
+
+        t.wal = Some(wal::read_only(&path).unwrap());
+
+        assert!(t.insert(record(30, "x")).is_err());
+        assert!(t.get(30).is_err());
+        assert!(t.update(record(3, "mudou")).is_err());
+        assert_eq!(t.get(3).unwrap().text, "texto 3");
+
+        // O registro volta com o rid que tinha
+        assert!(t.remove(3).is_err());
+        assert_eq!(t.records().search(&3).unwrap(), rid);
+        assert_eq!(t.records().len(), 20);
+        assert_eq!(t.check_consistency(), Ok(()));
+    }
+
+    #[test]
+    fn test_check_and_rebuild() {
+        let mut t = table();
+
+        t.register(
+            "flaky",
+            Box::new(Flaky {
+                entries: Vec::new(),
+                fail: "",
+                lose: 3,
+            }),
+        )
+        .unwrap();
+
+        assert!(t.check_consistency().is_err());
+
+        t.remove(3).unwrap();
+
+        assert_eq!(t.check_consistency(), Ok(()));
+        assert_eq!(t.find("flaky", &record(4, "")).unwrap()[0].nseq, 4);
+
+        t.rebuild().unwrap();
+
+        assert_eq!(t.check_consistency(), Ok(()));
+        assert!(matches!(
+            t.register("text", Box::new(HashText::new(1, 2).unwrap())),
+            Err(Error::DuplicateKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_save_load() {
+        let t = table();
+        let prefix = temp_path("table_test");
+
+        t.save(&prefix).unwrap();
+        std::fs::remove_file(format!("{prefix}_text.bin")).unwrap();
+
+        let mut t2 = Table::new(HashAlt1::new(1, 2).unwrap());
+
+        t2.register("alt2", Box::new(HashAlt2::new(1, 2).unwrap()))
+            .unwrap();
+        t2.register("text", Box::new(HashText::new(1, 2).unwrap()))
+            .unwrap();
+        t2.load(&prefix).unwrap();
+
+        // O indice sem arquivo e reconstruido
+        assert_eq!(t2.check_consistency(), Ok(()));
+        assert_eq!(t2.records().len(), 20);
+        assert_eq!(t2.find("text", &record(0, "texto 3")).unwrap().len(), 5);
+        assert_eq!(t2.find("alt2", &record(7, "texto 3")).unwrap()[0].nseq, 7);
+    }
+
+    #[test]
+    fn test_load_legacy() {
+        let prefix = temp_path("table_legacy");
+        remove_files(&prefix);
+
+        // hash_alt1.bin e hash_alt2.bin das primeiras versoes. O indice
+        // guardava balde e slot, entao e refeito a partir dos registros
+        let records: Vec<Record> = (0..6).map(|nseq| record(nseq, "texto")).collect();
+        let alt1 = crate::extendible_hash::legacy_image(0, 8, &[(0, records)], &[0]);
+
+        std::fs::write(format!("{prefix}_alt1.bin"), alt1).unwrap();
+        std::fs::write(format!("{prefix}_alt2.bin"), [0, 0, 1]).unwrap();
+
+        let mut t = empty();
+        t.load(&prefix).unwrap();
+
+        assert_eq!(t.check_consistency(), Ok(()));
+        assert_eq!(t.records().len(), 6);
+        assert_eq!(t.find("alt2", &record(4, "texto")).unwrap()[0].nseq, 4);
+        assert_eq!(t.find("text", &record(0, "texto")).unwrap().len(), 6);
+
+        remove_files(&prefix);
+    }
+
+    fn empty() -> Table {
+        let mut t = Table::new(HashAlt1::new(1, 2).unwrap());
+
+        t.register("alt2", Box::new(HashAlt2::new(1, 2).unwrap()))
+            .unwrap();
+        t.register("text", Box::new(HashText::new(1, 2).unwrap()))
+            .unwrap();
+
+        t
+    }
+
+    fn remove_files(prefix: &str) {
+        for suffix in [
+            "_alt1.bin",
+            "_alt2.bin",
+            "_text.bin",
+            "_alt1.pages",
+            "_alt2.pages",
+            "_text.pages",
+            "_alt1.pages.delta",
+            "_alt2.pages.delta",
+            "_text.pages.delta",
+            "_indexes.txt",
+            ".wal",
+        ] {
+            let _ = std::fs::remove_file(format!("{prefix}{suffix}"));
+        }
+    }
+
+    #[test]
+    fn test_recover() {
+        let prefix = temp_path("table_wal");
+        remove_files(&prefix);
+
+        {
+            let mut t = table();
+
+            t.start_log(&prefix, SyncPolicy::Always).unwrap();
+
+            for nseq in 20..40 {
+                t.insert(record(nseq, "depois")).unwrap();
+            }
+
+            t.update(record(3, "mudou")).unwrap();
+            t.remove(4).unwrap();
+            t.remove(25).unwrap();
+
+            // Mudanca rejeitada nao vai para o log
+            assert!(t.insert(record(5, "repetida")).is_err());
+        }
+
+        let mut t = empty();
+
+        assert_eq!(t.recover(&prefix, SyncPolicy::Always).unwrap(), 23);
+        assert_eq!(t.records().len(), 38);
+        assert_eq!(t.get(3).unwrap().text, "mudou");
+        assert!(t.get(4).is_err());
+        assert_eq!(t.find("text", &record(0, "depois")).unwrap().len(), 19);
+        assert_eq!(t.check_consistency(), Ok(()));
+
+        // Escrito sem esvaziar o log: reaplicar chega no mesmo estado
+        t.flush().unwrap();
+
+        let mut t = empty();
+
+        t.recover(&prefix, SyncPolicy::Always).unwrap();
+        assert_eq!(t.records().len(), 38);
+        assert_eq!(t.get(3).unwrap().text, "mudou");
+        assert!(t.get(25).is_err());
+        assert_eq!(t.check_consistency(), Ok(()));
+
+        t.sync().unwrap();
+        t.insert(record(4, "de volta")).unwrap();
+
+        let mut t = empty();
+
+        assert_eq!(t.recover(&prefix, SyncPolicy::Always).unwrap(), 1);
+        assert_eq!(t.get(4).unwrap().text, "de volta");
+    }
+
+    #[test]
+    fn test_recover_structure() {
+        let prefix = temp_path("table_wal_structure");
+        remove_files(&prefix);
+
+        let (depth, directory) = {
+            let mut t = empty();
+
+            t.start_log(&prefix, SyncPolicy::Never).unwrap();
+
+            for nseq in 0..40 {
+                t.insert(record(nseq, "x")).unwrap();
+            }
+
+            (t.records().global_depth(), t.records().directory().to_vec())
+        };
+
+        let (_, log) = Wal::open(format!("{prefix}.wal"), SyncPolicy::Never).unwrap();
+        let splits = log
+            .iter()
+            .filter(|(_, r)| matches!(r, LogRecord::Split { .. }))
+            .count();
+
+        // Cada divisao vem antes da insercao que a causou
+        assert!(splits > 0);
+        assert_eq!(log.last().unwrap().1, LogRecord::Insert(record(39, "x")));
+        assert!(log
+            .iter()
+            .any(|(_, r)| *r == LogRecord::Doubling { global_depth: 2 }));
+
+        let mut t = empty();
+
+        assert_eq!(t.recover(&prefix, SyncPolicy::Never).unwrap(), 40);
+        assert_eq!(t.records().global_depth(), depth);
+        assert_eq!(t.records().directory(), directory);
+        assert_eq!(t.check_consistency(), Ok(()));
+    }
+
+    /// Op `i` of `kill_worker`: inserts `i` and, when `i % 7 == 0`, removes
+    /// `i - 3`.
+    fn kill_op(t: &mut Table, i: i32) {
+        t.insert(record(i, &"x".repeat(i as usize % 300))).unwrap();
+
+        if i % 7 == 0 && i >= 3 {
+            t.remove(i - 3).unwrap();
+        }
+    }
+
+    /// Run by `test_kill_recovery` in a child process, which it kills.
+    #[test]
+    #[ignore]
+    fn kill_worker() {
+        let Ok(prefix) = std::env::var("KILL_PREFIX") else {
+            return;
+        };
+
+        let mut t = empty();
+        t.recover(&prefix, SyncPolicy::Always).unwrap();
+
+        for i in 0..100_000 {
+            kill_op(&mut t, i);
+
+            if i % 50 == 49 {
+                t.sync().unwrap();
+            }
+
+            eprintln!("acked {i}");
+        }
+    }
+
+    #[test]
+    fn test_kill_recovery() {
+        let mut rng = rand::thread_rng();
+
+        for round in 0..4 {
+            let prefix = temp_path(&format!("table_kill_{round}"));
+            remove_files(&prefix);
+
+            let mut child = Command::new(std::env::current_exe().unwrap())
+                .args([
+                    "table::test::kill_worker",
+                    "--exact",
+                    "--ignored",
+                    "--nocapture",
+                    "--test-threads=1",
+                ])
+                .env("KILL_PREFIX", &prefix)
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .spawn()
+                .unwrap();
+
+            // Mata o processo algum tempo depois da confirmacao numero `stop`
+            let stop = rng.gen_range(0..300);
+            let mut lines = BufReader::new(child.stderr.take().unwrap()).lines();
+            let mut acked = -1;
+
+            while acked < stop {
+                let line = lines.next().unwrap().unwrap();
+
+                if let Some(i) = line.split("acked ").nth(1) {
+                    acked = i.parse().unwrap();
+                }
+            }
+
+            child.kill().unwrap();
+            child.wait().unwrap();
+            drop(lines);
+
+            let mut t = empty();
+            t.recover(&prefix, SyncPolicy::Always).unwrap();
+
+            assert_eq!(t.check_consistency(), Ok(()));
+
+            // As operacoes depois da ultima confirmada so mexem nas chaves
+            // a partir de acked - 2
+            for k in 0..acked - 2 {
+                let removed = (k + 3) % 7 == 0 && k + 3 <= acked;
+
+                assert_eq!(
+                    t.get(k).is_ok(),
+                    !removed,
+                    "chave {k}, confirmadas ate {acked}"
+                );
+            }
+
+            // O log continua de onde parou
+            kill_op(&mut t, 100_000);
+            remove_files(&prefix);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::{fmt::Debug, io::Read};
+
+use crate::{error::Result, reader::Reader};
+
+/// Something that can be stored in a bucket and looked up by key.
+pub trait Keyed {
+    type Key: PartialEq + Clone;
+
+    /// Whether `insert` rejects a second value with the same key.
+    const UNIQUE: bool = true;
+
+    fn key(&self) -> &Self::Key;
+
+    /// Short text drawn inside the bucket slot by `Display`.
+    fn label(&self) -> String;
+}
+
+/// Variable length binary encoding of a record in a bucket page.
+pub trait Encode: Sized {
+    /// What the record format depends on, kept by the table and saved in its
+    /// header, e.g. a `Schema`. Types with a single format use `()`.
+    type Layout: Clone + Default + Debug;
+
+    /// Tag saved in file headers so a file of another record type is
+    /// rejected. The built-in types use 1 to 0x1f.
+    const KIND: u8 = 0;
+
+    /// Size a page is planned around: a table with bucket size `n` gets
+    /// pages that hold `n` records of this size.
+    fn nominal_size(layout: &Self::Layout) -> usize;
+
+    /// Appends the value to `buf`. Fails with `RecordTooLarge` if a field is
+    /// over its limit.
+    fn encode(&self, layout: &Self::Layout, buf: &mut Vec<u8>) -> Result<()>;
+
+    /// Reads a value written by `encode` in `len` bytes.
+    fn decode<R: Read>(layout: &Self::Layout, r: &mut Reader<R>, len: usize) -> Result<Self>;
+
+    /// Bytes of a slot in the headerless files of `format::LEGACY_VERSION`,
+    /// 0 for the types those files did not have.
+    const LEGACY_SIZE: usize = 0;
+
+    /// Reads a slot of `LEGACY_SIZE` bytes, `None` if it is empty.
+    fn decode_legacy<R: Read>(r: &mut Reader<R>) -> Result<Option<Self>> {
+        Err(r.corrupt("tipo sem formato antigo"))
+    }
+
+    fn encoded_len(&self, layout: &Self::Layout) -> usize {
+        let mut buf = Vec::new();
+        let _ = self.encode(layout, &mut buf);
+        buf.len()
+    }
+
+    fn validate(&self, layout: &Self::Layout) -> Result<()> {
+        self.encode(layout, &mut Vec::new())
+    }
+
+    fn write_layout(_layout: &Self::Layout, _buf: &mut Vec<u8>) {}
+
+    fn read_layout<R: Read>(_r: &mut Reader<R>) -> Result<Self::Layout> {
+        Ok(Self::Layout::default())
+    }
+}
@@ -0,0 +1,462 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::{crc32::crc32, error::Result, reader::Reader, record::Record, traits::Encode};
+
+/// Log sequence number. Grows by one per log record and never repeats,
+/// not even after the log is reset by a checkpoint.
+pub type Lsn = u64;
+
+/// When appended records are forced to disk with `fsync`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncPolicy {
+    /// After every change: nothing acknowledged is lost.
+    Always,
+    /// After every `n` changes: a crash loses at most the last `n - 1`.
+    Every(usize),
+    /// Left to the operating system. Survives the process being killed, not
+    /// the machine going down.
+    Never,
+}
+
+/// A change to a `Table`. The splits and directory doublings of its
+/// primary index are logged before the change that caused them; merges are
+/// not, `redo` makes them again with the removes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LogRecord {
+    Insert(Record),
+    Update(Record),
+    Remove(i32),
+    /// Bucket `bucket` was split, going to `local_depth`.
+    Split {
+        bucket: u32,
+        local_depth: u8,
+    },
+    /// The directory was doubled to `global_depth`.
+    Doubling {
+        global_depth: u8,
+    },
+    /// The table was saved and the log emptied.
+    Checkpoint,
+}
+
+impl LogRecord {
+    fn kind(&self) -> u8 {
+        match self {
+            LogRecord::Insert(_) => 1,
+            LogRecord::Update(_) => 2,
+            LogRecord::Remove(_) => 3,
+            LogRecord::Split { .. } => 4,
+            LogRecord::Doubling { .. } => 5,
+            LogRecord::Checkpoint => 6,
+        }
+    }
+
+    /// A change that `redo` has to apply again.
+    pub fn is_change(&self) -> bool {
+        matches!(
+            self,
+            LogRecord::Insert(_) | LogRecord::Update(_) | LogRecord::Remove(_)
+        )
+    }
+}
+
+/// Append-only log of the changes made since the last checkpoint.
+///
+/// `| 8B lsn | 1B tipo | 4B len | payload | 4B crc32 |` per record, the CRC
+/// covering everything before it. A crash in the middle of an append leaves
+/// a torn record at the end, which `open` drops.
+pub struct Wal {
+    file: File,
+    next_lsn: Lsn,
+    policy: SyncPolicy,
+    /// Records appended since the last fsync.
+    unsynced: usize,
+    /// Bytes of the records written whole.
+    len: u64,
+    /// A failed write may have left bytes after `len`.
+    torn: bool,
+}
+
+/// Bytes of a log record before the payload.
+const HEADER: usize = 8 + 1 + 4;
+
+impl Wal {
+    /// Opens (or creates) the log at `path`, returning it with the records
+    /// it holds, in order.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        policy: SyncPolicy,
+    ) -> Result<(Wal, Vec<(Lsn, LogRecord)>)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let mut records = Vec::new();
+        let mut offset = 0;
+        let mut next_lsn = 1;
+
+        // Para no primeiro registro incompleto ou com CRC errado
+        while let Some((lsn, record, len)) = decode(&bytes[offset..], offset as u64) {
+            if lsn < next_lsn {
+                break;
+            }
+
+            next_lsn = lsn + 1;
+            offset += len;
+            records.push((lsn, record));
+        }
+
+        // Descarta o resto para as proximas escritas continuarem do ultimo
+        // registro valido
+        file.set_len(offset as u64)?;
+        file.seek(SeekFrom::End(0))?;
+
+        let wal = Wal {
+            file,
+            next_lsn,
+            policy,
+            unsynced: 0,
+            len: offset as u64,
+            torn: false,
+        };
+
+        Ok((wal, records))
+    }
+
+    pub fn next_lsn(&self) -> Lsn {
+        self.next_lsn
+    }
+
+    pub fn policy(&self) -> SyncPolicy {
+        self.policy
+    }
+
+    /// Appends the records of one change and syncs as the policy says. Returns
+    /// the LSN of the last one.
+    ///
+    /// If the write or the sync fails the log is cut back to where it was and
+    /// the LSNs are not used, so later records do not land after a torn one.
+    pub fn append(&mut self, records: &[LogRecord]) -> Result<Lsn> {
+        if self.torn {
+            self.truncate()?;
+        }
+
+        let mut buf = Vec::new();
+        let mut lsn = self.next_lsn;
+
+        for record in records {
+            encode(lsn, record, &mut buf)?;
+            lsn += 1;
+        }
+
+        if let Err(e) = self.write(&buf) {
+            // Se nao der para cortar agora, o proximo append tenta de novo
+            self.torn = true;
+            let _ = self.truncate();
+
+            return Err(e);
+        }
+
+        self.next_lsn = lsn;
+        self.len += buf.len() as u64;
+
+        Ok(lsn - 1)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<()> {
+        self.file.write_all(buf)?;
+        self.unsynced += 1;
+
+        let sync = match self.policy {
+            SyncPolicy::Always => true,
+            SyncPolicy::Every(n) => self.unsynced >= n,
+            SyncPolicy::Never => false,
+        };
+
+        if sync {
+            self.sync()?;
+        }
+
+        Ok(())
+    }
+
+    /// Drops whatever follows the last record written whole.
+    fn truncate(&mut self) -> Result<()> {
+        self.file.set_len(self.len)?;
+        self.file.seek(SeekFrom::Start(self.len))?;
+        self.torn = false;
+
+        Ok(())
+    }
+
+    /// Forces the appended records to disk.
+    pub fn sync(&mut self) -> Result<()> {
+        self.file.sync_data()?;
+        self.unsynced = 0;
+
+        Ok(())
+    }
+
+    /// Empties the log after the table was saved. A `Checkpoint` record keeps
+    /// the LSNs going up.
+    pub fn reset(&mut self) -> Result<()> {
+        self.len = 0;
+        self.torn = true;
+        self.truncate()?;
+
+        let mut buf = Vec::new();
+        encode(self.next_lsn, &LogRecord::Checkpoint, &mut buf)?;
+
+        if let Err(e) = self
+            .file
+            .write_all(&buf)
+            .map_err(Into::into)
+            .and_then(|_| self.sync())
+        {
+            self.torn = true;
+            let _ = self.truncate();
+
+            return Err(e);
+        }
+
+        self.next_lsn += 1;
+        self.len = buf.len() as u64;
+
+        Ok(())
+    }
+}
+
+fn encode(lsn: Lsn, record: &LogRecord, buf: &mut Vec<u8>) -> Result<()> {
+    let start = buf.len();
+
+    buf.extend_from_slice(&lsn.to_be_bytes());
+    buf.push(record.kind());
+    buf.extend_from_slice(&[0; 4]);
+
+    match record {
+        LogRecord::Insert(r) | LogRecord::Update(r) => r.encode(&(), buf)?,
+        LogRecord::Remove(nseq) => buf.extend_from_slice(&nseq.to_be_bytes()),
+        LogRecord::Split {
+            bucket,
+            local_depth,
+        } => {
+            buf.extend_from_slice(&bucket.to_be_bytes());
+            buf.push(*local_depth);
+        }
+        LogRecord::Doubling { global_depth } => buf.push(*global_depth),
+        LogRecord::Checkpoint => {}
+    }
+
+    let len = (buf.len() - start - HEADER) as u32;
+    buf[start + 9..start + 13].copy_from_slice(&len.to_be_bytes());
+
+    let crc = crc32(&buf[start..]);
+    buf.extend_from_slice(&crc.to_be_bytes());
+
+    Ok(())
+}
+
+/// The record at the start of `bytes` and its length, or `None` if it is
+/// torn or damaged.
+fn decode(bytes: &[u8], offset: u64) -> Option<(Lsn, LogRecord, usize)> {
+    if bytes.len() < HEADER + 4 {
+        return None;
+    }
+
+    let len = u32::from_be_bytes(bytes[9..13].try_into().unwrap()) as usize;
+    let end = HEADER.checked_add(len)?;
+
+    if bytes.len() < end + 4 {
+        return None;
+    }
+
+    let crc = u32::from_be_bytes(bytes[end..end + 4].try_into().unwrap());
+
+    if crc32(&bytes[..end]) != crc {
+        return None;
+    }
+
+    let lsn = u64::from_be_bytes(bytes[..8].try_into().unwrap());
+    let mut r = Reader::at(&bytes[HEADER..end], offset + HEADER as u64);
+
+    let record = match bytes[8] {
+        1 => LogRecord::Insert(Record::decode(&(), &mut r, len).ok()?),
+        2 => LogRecord::Update(Record::decode(&(), &mut r, len).ok()?),
+        3 => LogRecord::Remove(r.read_i32().ok()?),
+        4 => LogRecord::Split {
+            bucket: r.read_u32().ok()?,
+            local_depth: r.read_u8().ok()?,
+        },
+        5 => LogRecord::Doubling {
+            global_depth: r.read_u8().ok()?,
+        },
+        6 => LogRecord::Checkpoint,
+        _ => return None,
+    };
+
+    Some((lsn, record, end + 4))
+}
+
+/// A log on a file opened read only, whose appends fail.
+#[cfg(test)]
+pub(crate) fn read_only<P: AsRef<Path>>(path: P) -> Result<Wal> {
+    Ok(Wal {
+        file: File::open(path)?,
+        next_lsn: 1,
+        policy: SyncPolicy::Always,
+        unsynced: 0,
+        len: 0,
+        torn: false,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::temp_path;
+
+    fn record(nseq: i32) -> Record {
+        Record {
+            nseq,
+            text: format!("registro {nseq}"),
+        }
+    }
+
+    #[test]
+    fn test_append_reopen() {
+        let path = temp_path("wal_test.wal");
+        let _ = std::fs::remove_file(&path);
+
+        let (mut wal, records) = Wal::open(&path, SyncPolicy::Always).unwrap();
+
+        assert!(records.is_empty());
+        assert_eq!(wal.append(&[LogRecord::Insert(record(1))]).unwrap(), 1);
+        assert_eq!(
+            wal.append(&[
+                LogRecord::Insert(record(2)),
+                LogRecord::Insert(record(3)),
+                LogRecord::Update(record(2)),
+            ])
+            .unwrap(),
+            4
+        );
+        wal.append(&[LogRecord::Remove(1)]).unwrap();
+        drop(wal);
+
+        let (mut wal, records) = Wal::open(&path, SyncPolicy::Every(2)).unwrap();
+
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[1], (2, LogRecord::Insert(record(2))));
+        assert_eq!(records[4], (5, LogRecord::Remove(1)));
+
+        // O checkpoint esvazia o log sem voltar os LSNs
+        wal.reset().unwrap();
+        assert_eq!(wal.append(&[LogRecord::Update(record(2))]).unwrap(), 7);
+        drop(wal);
+
+        let (_, records) = Wal::open(&path, SyncPolicy::Never).unwrap();
+
+        assert_eq!(
+            records,
+            [
+                (6, LogRecord::Checkpoint),
+                (7, LogRecord::Update(record(2)))
+            ]
+        );
+
+        // Divisoes e duplicacoes vao no mesmo append da mudanca
+        let mut wal = Wal::open(&path, SyncPolicy::Never).unwrap().0;
+        let split = [
+            LogRecord::Doubling { global_depth: 3 },
+            LogRecord::Split {
+                bucket: 5,
+                local_depth: 3,
+            },
+            LogRecord::Insert(record(4)),
+        ];
+
+        assert_eq!(wal.append(&split).unwrap(), 10);
+        drop(wal);
+
+        let (_, records) = Wal::open(&path, SyncPolicy::Never).unwrap();
+        let records: Vec<_> = records.into_iter().skip(2).map(|(_, r)| r).collect();
+
+        assert_eq!(records, split);
+        assert!(!split[0].is_change() && !split[1].is_change());
+    }
+
+    #[test]
+    fn test_torn_tail() {
+        let path = temp_path("wal_torn.wal");
+        let _ = std::fs::remove_file(&path);
+
+        let (mut wal, _) = Wal::open(&path, SyncPolicy::Always).unwrap();
+
+        for nseq in 0..3 {
+            wal.append(&[LogRecord::Insert(record(nseq))]).unwrap();
+        }
+        drop(wal);
+
+        // Corta o ultimo registro no meio
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 5]).unwrap();
+
+        let (mut wal, records) = Wal::open(&path, SyncPolicy::Always).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(wal.append(&[LogRecord::Remove(0)]).unwrap(), 3);
+        drop(wal);
+
+        // Um byte trocado invalida o CRC do registro e dos seguintes
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[HEADER + 2] ^= 1;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let (_, records) = Wal::open(&path, SyncPolicy::Always).unwrap();
+
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn test_failed_append() {
+        let path = temp_path("wal_failed.wal");
+        let _ = std::fs::remove_file(&path);
+
+        let (mut wal, _) = Wal::open(&path, SyncPolicy::Always).unwrap();
+        wal.append(&[LogRecord::Insert(record(1))]).unwrap();
+
+        // Escrita que falha: o arquivo so aceita leitura
+        let writable = std::mem::replace(&mut wal.file, File::open(&path).unwrap());
+
+        assert!(wal.append(&[LogRecord::Insert(record(2))]).is_err());
+        assert_eq!(wal.next_lsn(), 2);
+
+        // O que a escrita deixou no fim antes de falhar
+        let mut garbage = OpenOptions::new().append(true).open(&path).unwrap();
+        garbage.write_all(&[7; 10]).unwrap();
+
+        wal.file = writable;
+        wal.file.seek(SeekFrom::End(0)).unwrap();
+
+        assert_eq!(wal.append(&[LogRecord::Remove(1)]).unwrap(), 2);
+        drop(wal);
+
+        // O registro confirmado depois da falha nao se perde
+        let (_, records) = Wal::open(&path, SyncPolicy::Always).unwrap();
+
+        assert_eq!(
+            records,
+            [(1, LogRecord::Insert(record(1))), (2, LogRecord::Remove(1))]
+        );
+    }
+}